@@ -21,12 +21,6 @@
 // SOFTWARE.
 // be used to understand and use DataFrame
 use anyhow::{Ok, Result};
-use polars::prelude::*;
-use std::{
-    env,
-    fs::File,
-    io::{Cursor, Read},
-};
 
 use sqltools::data_set::query;
 
@@ -37,7 +31,7 @@ async fn main() -> Result<()> {
     // let url = "https://raw.githubusercontent.com/owid/covid-19-data/master/public/data/latest/owid-covid-latest.csv";
     // let curr_pwd = env::current_exe()?;
     // println!("{:?}", curr_pwd);
-    let url = "file://../../../owid-covid-latest.csv";
+    let url = concat!(env!("CARGO_MANIFEST_DIR"), "/owid-covid-latest.csv");
     // let mut buf = String::new();
     // let _ = File::open(url)?.read_to_string(&mut buf)?;
     // println!("{}", buf);
@@ -45,7 +39,7 @@ async fn main() -> Result<()> {
     // println!("{}", data);
     let sql = format!(
         "SELECT location, total_cases, new_cases, total_deaths, new_deaths \
-    FROM {url} where new_deaths >= 500 ORDER BY new_cases DESC"
+    FROM '{url}' where new_deaths >= 500 ORDER BY new_cases DESC"
     );
 
     let ds = query(sql).await?;
@@ -28,6 +28,6 @@ fn main() {
     where a > b and b < 100 and c between 10 and 20 \
     order by a desc, b \
     limit 50 offset 10;";
-    let ast = Parser::parse_sql(&GenericDialect::default(), sql);
+    let ast = Parser::parse_sql(&GenericDialect, sql);
     println!("{:#?}", ast)
 }
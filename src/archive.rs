@@ -0,0 +1,475 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::{
+    io::{Cursor, Read},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use glob::{MatchOptions, Pattern};
+use percent_encoding::percent_decode_str;
+use tokio::sync::OnceCell;
+use url::Url;
+
+use crate::{
+    compression::decompress,
+    error::BoxError,
+    fetcher::{Content, Fetcher, Metadata},
+};
+
+/// Members are matched per path segment, `*` does not cross a `/`.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// How many bytes of archives an [`ArchiveFetcher`] keeps, the least recently
+/// used is dropped first. 64MiB.
+const ARCHIVE_BUDGET: usize = 64 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    /// optionally compressed, e.g. `.tar.gz`.
+    Tar,
+}
+
+/// Reads a member of a zip or tar archive fetched by the wrapped fetcher,
+/// e.g. `zip+file:///data/bundle.zip#owid/latest.csv`. The scheme is the
+/// archive kind followed by the scheme of the inner fetcher and the fragment
+/// names the member, or a glob pattern matching members.
+///
+/// The fragment may be left out for archives holding a single file. Recently
+/// read archives with a known modification time are kept, so listing and
+/// reading their members, even concurrently, only downloads each of them once
+/// for as long as they are unchanged.
+pub struct ArchiveFetcher<F> {
+    kind: ArchiveKind,
+    inner: F,
+    /// the archives by url and modification time, the most recently used
+    /// last. A download in flight is awaited by the other readers.
+    archives: Mutex<Vec<Cached>>,
+}
+
+/// An archive as downloaded, or being downloaded.
+struct Cached {
+    url: Url,
+    modified: SystemTime,
+    bytes: Arc<OnceCell<Bytes>>,
+}
+
+impl<F> ArchiveFetcher<F> {
+    pub fn new(kind: ArchiveKind, inner: F) -> Self {
+        ArchiveFetcher {
+            kind,
+            inner,
+            archives: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn zip(inner: F) -> Self {
+        Self::new(ArchiveKind::Zip, inner)
+    }
+
+    pub fn tar(inner: F) -> Self {
+        Self::new(ArchiveKind::Tar, inner)
+    }
+}
+
+impl<F> ArchiveFetcher<F>
+where
+    F: Fetcher,
+    F::Error: Into<BoxError>,
+{
+    /// The (decompressed) archive at `url`. It is fetched every time, but
+    /// only downloaded when its modification time is unknown or changed.
+    async fn archive(&self, url: &Url) -> Result<Bytes, BoxError> {
+        let content = self.inner.fetch(url).await.map_err(Into::into)?;
+        let Some(modified) = content.metadata.last_modified else {
+            return Ok(decompress(content, None).await?.bytes().await?);
+        };
+        let cell = {
+            let mut archives = self.archives.lock().unwrap();
+            let cached = match archives
+                .iter()
+                .position(|cached| &cached.url == url && cached.modified == modified)
+            {
+                Some(index) => archives.remove(index),
+                None => Cached {
+                    url: url.clone(),
+                    modified,
+                    bytes: Arc::default(),
+                },
+            };
+            // older versions of the archive are never read again.
+            archives.retain(|older| &older.url != url);
+            let cell = cached.bytes.clone();
+            archives.push(cached);
+            cell
+        };
+        let bytes = cell
+            .get_or_try_init(|| async { decompress(content, None).await?.bytes().await })
+            .await?
+            .clone();
+        self.evict();
+        Ok(bytes)
+    }
+
+    /// Drop the least recently used archives until they fit in the budget.
+    fn evict(&self) {
+        let mut archives = self.archives.lock().unwrap();
+        let size = |archives: &[Cached]| -> usize {
+            archives
+                .iter()
+                .filter_map(|cached| cached.bytes.get().map(Bytes::len))
+                .sum()
+        };
+        while size(&archives) > ARCHIVE_BUDGET {
+            archives.remove(0);
+        }
+    }
+
+    /// The names of the members matched by the fragment of `url`, along with
+    /// the archive.
+    async fn matches(&self, url: &Url) -> Result<(Vec<String>, Bytes), BoxError> {
+        let (archive, member) = split(url)?;
+        let bytes = self.archive(&archive).await?;
+        let members = self.kind.members(&bytes)?;
+        let matched: Vec<_> = match &member {
+            Some(member) if is_glob(member) => {
+                let pattern = Pattern::new(member)?;
+                members
+                    .iter()
+                    .filter(|name| pattern.matches_with(name, MATCH_OPTIONS))
+                    .cloned()
+                    .collect()
+            }
+            Some(member) => members
+                .iter()
+                .filter(|name| *name == member)
+                .cloned()
+                .collect(),
+            None if members.len() == 1 => members.clone(),
+            None => {
+                return Err(format!(
+                    "name one of the archive members after a `#`: {}",
+                    members.join(", ")
+                )
+                .into())
+            }
+        };
+        if matched.is_empty() {
+            return Err(format!(
+                "no member matching `{}`, the archive holds: {}",
+                member.unwrap_or_default(),
+                members.join(", ")
+            )
+            .into());
+        }
+        Ok((matched, bytes))
+    }
+}
+
+#[async_trait]
+impl<F> Fetcher for ArchiveFetcher<F>
+where
+    F: Fetcher,
+    F::Error: Into<BoxError>,
+{
+    type Error = BoxError;
+
+    async fn fetch(&self, url: &Url) -> Result<Content, Self::Error> {
+        let (matched, bytes) = self.matches(url).await?;
+        let [member] = matched.as_slice() else {
+            return Err(format!("`{url}` matches {} archive members", matched.len()).into());
+        };
+        let data = self.kind.read(&bytes, member)?;
+        let metadata = Metadata {
+            file_name: member.rsplit('/').next().map(str::to_owned),
+            ..Default::default()
+        };
+        Ok(Content::from_bytes(metadata, data))
+    }
+
+    async fn list(&self, url: &Url) -> Result<Vec<Url>, Self::Error> {
+        if !url.fragment().is_some_and(is_glob) {
+            return Ok(vec![url.clone()]);
+        }
+        let (matched, _) = self.matches(url).await?;
+        Ok(matched
+            .into_iter()
+            .map(|member| {
+                let mut url = url.clone();
+                url.set_fragment(Some(&member));
+                url
+            })
+            .collect())
+    }
+}
+
+impl ArchiveKind {
+    /// The names of the files in `archive`, in archive order.
+    fn members(&self, archive: &Bytes) -> Result<Vec<String>, BoxError> {
+        match self {
+            ArchiveKind::Zip => {
+                let zip = zip::ZipArchive::new(Cursor::new(archive))?;
+                Ok(zip
+                    .file_names()
+                    .filter(|name| !name.ends_with('/'))
+                    .map(str::to_owned)
+                    .collect())
+            }
+            ArchiveKind::Tar => {
+                let mut tar = tar::Archive::new(Cursor::new(archive));
+                let mut names = vec![];
+                for entry in tar.entries()? {
+                    let entry = entry?;
+                    if entry.header().entry_type().is_file() {
+                        names.push(entry.path()?.to_string_lossy().into_owned());
+                    }
+                }
+                Ok(names)
+            }
+        }
+    }
+
+    fn read(&self, archive: &Bytes, member: &str) -> Result<Vec<u8>, BoxError> {
+        let mut data = vec![];
+        match self {
+            ArchiveKind::Zip => {
+                let mut zip = zip::ZipArchive::new(Cursor::new(archive))?;
+                zip.by_name(member)?.read_to_end(&mut data)?;
+            }
+            ArchiveKind::Tar => {
+                let mut tar = tar::Archive::new(Cursor::new(archive));
+                for entry in tar.entries()? {
+                    let mut entry = entry?;
+                    if entry.path()?.to_string_lossy() == member {
+                        entry.read_to_end(&mut data)?;
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// The url of the archive and the (decoded) member named by the fragment.
+fn split(url: &Url) -> Result<(Url, Option<String>), BoxError> {
+    let member = url
+        .fragment()
+        .filter(|fragment| !fragment.is_empty())
+        .map(|fragment| percent_decode_str(fragment).decode_utf8())
+        .transpose()?
+        .map(|member| member.into_owned());
+    let mut archive = url.clone();
+    archive.set_fragment(None);
+    let inner = archive
+        .as_str()
+        .split_once('+')
+        .map(|(_, inner)| inner)
+        .ok_or_else(|| format!("not an archive url: {url}"))?;
+    Ok((Url::parse(inner)?, member))
+}
+
+fn is_glob(member: &str) -> bool {
+    member.contains(['*', '?', '['])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{error::Error, session::Session, test_util::TempDir};
+    use std::{
+        io::Write,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use futures_util::stream;
+
+    /// Serves the same archive for every url, counting the downloads.
+    struct Bundle {
+        archive: Vec<u8>,
+        modified: Mutex<Option<SystemTime>>,
+        downloads: Arc<AtomicUsize>,
+    }
+
+    impl Bundle {
+        fn new(archive: Vec<u8>) -> Self {
+            Bundle {
+                archive,
+                modified: Mutex::new(Some(SystemTime::UNIX_EPOCH)),
+                downloads: Arc::default(),
+            }
+        }
+
+        fn downloads(&self) -> usize {
+            self.downloads.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl Fetcher for Bundle {
+        type Error = BoxError;
+
+        async fn fetch(&self, _url: &Url) -> Result<Content, Self::Error> {
+            let metadata = Metadata {
+                last_modified: *self.modified.lock().unwrap(),
+                ..Default::default()
+            };
+            let (archive, downloads) = (Bytes::from(self.archive.clone()), self.downloads.clone());
+            let body = stream::once(async move {
+                downloads.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                Ok(archive)
+            });
+            Ok(Content::new(metadata, Box::pin(body)))
+        }
+    }
+
+    fn zipped(members: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        for (name, data) in members {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(data.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[tokio::test]
+    async fn it_reads_zip_members() {
+        let fetcher = ArchiveFetcher::zip(Bundle::new(zipped(&[
+            ("owid/latest.csv", "a\n1\n"),
+            ("owid/2023.csv", "a\n2\n"),
+            ("README", "hello"),
+        ])));
+        let url = Url::parse("zip+file:///bundle.zip#owid/latest.csv").unwrap();
+        let content = fetcher.fetch(&url).await.unwrap();
+        assert_eq!(content.metadata.file_name.as_deref(), Some("latest.csv"));
+        assert_eq!(content.bytes().await.unwrap(), "a\n1\n");
+
+        let url = Url::parse("zip+file:///bundle.zip#owid/*.csv").unwrap();
+        let listed = fetcher.list(&url).await.unwrap();
+        assert_eq!(
+            listed.iter().map(Url::as_str).collect::<Vec<_>>(),
+            [
+                "zip+file:///bundle.zip#owid/latest.csv",
+                "zip+file:///bundle.zip#owid/2023.csv"
+            ]
+        );
+
+        let url = Url::parse("zip+file:///bundle.zip#missing.csv").unwrap();
+        let err = fetcher.fetch(&url).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no member matching `missing.csv`, the archive holds: \
+            owid/latest.csv, owid/2023.csv, README"
+        );
+        assert_eq!(fetcher.inner.downloads(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_downloads_unchanged_archives_once() {
+        let fetcher = ArchiveFetcher::zip(Bundle::new(zipped(&[
+            ("a.csv", "a\n1\n"),
+            ("b.csv", "a\n2\n"),
+        ])));
+        let member = |url: &str| {
+            let url = Url::parse(url).unwrap();
+            let fetcher = &fetcher;
+            async move { fetcher.fetch(&url).await.unwrap().bytes().await.unwrap() }
+        };
+        let (a, b, other) = tokio::join!(
+            member("zip+file:///one.zip#a.csv"),
+            member("zip+file:///one.zip#b.csv"),
+            member("zip+file:///two.zip#a.csv"),
+        );
+        assert_eq!(
+            (a, b, other),
+            ("a\n1\n".into(), "a\n2\n".into(), "a\n1\n".into())
+        );
+        assert_eq!(fetcher.inner.downloads(), 2);
+
+        // a changed archive is downloaded again, as is one of unknown age.
+        *fetcher.inner.modified.lock().unwrap() = Some(SystemTime::now());
+        member("zip+file:///one.zip#a.csv").await;
+        member("zip+file:///one.zip#b.csv").await;
+        assert_eq!(fetcher.inner.downloads(), 3);
+        assert_eq!(fetcher.archives.lock().unwrap().len(), 2);
+        *fetcher.inner.modified.lock().unwrap() = None;
+        member("zip+file:///one.zip#a.csv").await;
+        member("zip+file:///one.zip#a.csv").await;
+        assert_eq!(fetcher.inner.downloads(), 5);
+    }
+
+    #[tokio::test]
+    async fn it_reads_tar_members() {
+        let mut tar = tar::Builder::new(vec![]);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_cksum();
+        tar.append_data(&mut header, "data.csv", "a\n1\n".as_bytes())
+            .unwrap();
+        let fetcher = ArchiveFetcher::tar(Bundle::new(tar.into_inner().unwrap()));
+
+        let url = Url::parse("tar+https://example.com/bundle.tar").unwrap();
+        let content = fetcher.fetch(&url).await.unwrap();
+        assert_eq!(content.bytes().await.unwrap(), "a\n1\n");
+    }
+
+    #[tokio::test]
+    async fn it_reads_archive_members() {
+        use std::io::Write;
+
+        let dir = TempDir::new("zip");
+        let bundle = dir.join("bundle.zip");
+        let mut zip = ::zip::ZipWriter::new(std::fs::File::create(&bundle).unwrap());
+        for (name, data) in [("2023.csv", "a,b\n1,x\n"), ("2024.csv", "a,c\n2,true\n")] {
+            zip.start_file(name, ::zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(data.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let mut session = Session::default();
+        let sql = format!(
+            "select a, b from \"zip+file://{}#2024.csv\"",
+            bundle.display()
+        );
+        let err = session.query(sql).await.unwrap_err();
+        assert!(matches!(err, Error::UnknownColumn { .. }));
+
+        let sql = format!(
+            "select a, b, c from \"zip+file://{}#20*.csv\" order by a",
+            bundle.display()
+        );
+        let ds = session.query(sql).await.unwrap();
+        assert_eq!(ds.shape(), (2, 3));
+        assert_eq!(ds.column("c").unwrap().null_count(), 1);
+    }
+}
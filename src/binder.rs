@@ -0,0 +1,236 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::ops::ControlFlow;
+
+use polars::prelude::Schema;
+use sqlparser::ast::{
+    visit_expressions_mut, Expr as SqlExpr, GroupByExpr, Ident, Query, SelectItem, SetExpr,
+    Statement,
+};
+
+use crate::error::{Error, Result};
+
+/// Maximum number of "did you mean" suggestions reported for a missing column.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Resolves the identifiers of a statement against the schema of the loaded
+/// data source, before the statement is translated into a polars plan.
+///
+/// Unquoted identifiers are matched case-insensitively (an exact match wins),
+/// quoted identifiers must match exactly. Resolved identifiers are rewritten
+/// to the column name found in the schema.
+pub struct Binder<'a> {
+    schema: &'a Schema,
+    /// leave the identifiers missing from the schema as they are.
+    partial: bool,
+}
+
+impl<'a> Binder<'a> {
+    pub fn new(schema: &'a Schema) -> Self {
+        Binder {
+            schema,
+            partial: false,
+        }
+    }
+
+    /// A binder resolving only the identifiers found in `schema`, e.g. the
+    /// partition columns of a source before its files are loaded.
+    pub fn partial(schema: &'a Schema) -> Self {
+        Binder {
+            schema,
+            partial: true,
+        }
+    }
+
+    pub fn bind(&self, statement: &mut Statement) -> Result<()> {
+        match statement {
+            Statement::Query(q) => self.bind_query(q),
+            _ => Ok(()),
+        }
+    }
+
+    fn bind_query(&self, query: &mut Query) -> Result<()> {
+        let mut aliases = vec![];
+        if let SetExpr::Select(select) = query.body.as_mut() {
+            for item in select.projection.iter_mut() {
+                match item {
+                    SelectItem::UnnamedExpr(expr) => self.bind_expr(expr)?,
+                    SelectItem::ExprWithAlias { expr, alias } => {
+                        self.bind_expr(expr)?;
+                        aliases.push(alias.value.clone());
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(expr) = select.selection.as_mut() {
+                self.bind_expr(expr)?;
+            }
+            if let GroupByExpr::Expressions(exprs) = &mut select.group_by {
+                for expr in exprs.iter_mut() {
+                    self.bind_expr(expr)?;
+                }
+            }
+            if let Some(expr) = select.having.as_mut() {
+                self.bind_expr(expr)?;
+            }
+        }
+        for order_by in query.order_by.iter_mut() {
+            match &order_by.expr {
+                SqlExpr::Identifier(id) if aliases.contains(&id.value) => {}
+                _ => self.bind_expr(&mut order_by.expr)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn bind_expr(&self, expr: &mut SqlExpr) -> Result<()> {
+        let flow = visit_expressions_mut(expr, |e| {
+            if let SqlExpr::Identifier(id) = e {
+                match self.resolve(id) {
+                    Err(e) if !self.partial => return ControlFlow::Break(e),
+                    _ => {}
+                }
+            }
+            ControlFlow::Continue(())
+        });
+        match flow {
+            ControlFlow::Break(e) => Err(e),
+            ControlFlow::Continue(()) => Ok(()),
+        }
+    }
+
+    /// Rewrite `id` to the matching column name, or report the closest names.
+    pub fn resolve(&self, id: &mut Ident) -> Result<()> {
+        if self.schema.contains(&id.value) {
+            return Ok(());
+        }
+        if id.quote_style.is_none() {
+            let mut matches = self
+                .schema
+                .iter_names()
+                .filter(|name| name.eq_ignore_ascii_case(&id.value));
+            if let (Some(name), None) = (matches.next(), matches.next()) {
+                id.value = name.to_string();
+                return Ok(());
+            }
+        }
+        Err(Error::unknown_column(
+            id.value.clone(),
+            suggest(&id.value, self.schema.iter_names().map(|n| n.as_str())),
+        ))
+    }
+}
+
+/// The candidates closest to `name` by case-insensitive edit distance, nearest
+/// first. Candidates further away than a third of the name length are ignored.
+pub fn suggest<'b>(name: &str, candidates: impl Iterator<Item = &'b str>) -> Vec<String> {
+    let name = name.to_lowercase();
+    let threshold = (name.chars().count() / 3).max(2);
+    let mut scored: Vec<_> = candidates
+        .map(|c| (edit_distance(&name, &c.to_lowercase()), c))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect();
+    scored.sort_by_key(|(distance, _)| *distance);
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c.to_owned())
+        .collect()
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dialect::OrinDialect;
+    use polars::prelude::{DataType, Field};
+    use sqlparser::parser::Parser;
+
+    fn schema() -> Schema {
+        Schema::from_iter([
+            Field::new("location", DataType::String),
+            Field::new("new_cases", DataType::Float64),
+            Field::new("new_deaths", DataType::Float64),
+            Field::new("Total", DataType::Float64),
+        ])
+    }
+
+    fn bind(sql: &str) -> Result<String> {
+        let mut statement = Parser::parse_sql(&OrinDialect, sql).unwrap().remove(0);
+        Binder::new(&schema()).bind(&mut statement)?;
+        Ok(statement.to_string())
+    }
+
+    #[test]
+    fn it_resolves_unquoted_identifiers_case_insensitively() {
+        assert_eq!(
+            bind("select LOCATION l, total from t where New_Cases > 1 order by l").unwrap(),
+            "SELECT location AS l, Total FROM t WHERE new_cases > 1 ORDER BY l"
+        );
+    }
+
+    #[test]
+    fn it_matches_quoted_identifiers_exactly() {
+        let err = bind("select \"total\" from t").unwrap_err();
+        assert!(
+            matches!(err, Error::UnknownColumn { ref suggestions, .. } if suggestions == &["Total"])
+        );
+    }
+
+    #[test]
+    fn it_leaves_unknown_identifiers_when_partial() {
+        let sql = "select Cases from t where YEAR = 2024 and \"Month\" = 1";
+        let mut statement = Parser::parse_sql(&OrinDialect, sql).unwrap().remove(0);
+        let schema = Schema::from_iter([
+            Field::new("year", DataType::Int64),
+            Field::new("month", DataType::Int64),
+        ]);
+        Binder::partial(&schema).bind(&mut statement).unwrap();
+        assert_eq!(
+            statement.to_string(),
+            "SELECT Cases FROM t WHERE year = 2024 AND \"Month\" = 1"
+        );
+    }
+
+    #[test]
+    fn it_suggests_the_nearest_columns() {
+        let err = bind("select new_death from t").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown column `new_death`, did you mean `new_deaths`?"
+        );
+    }
+}
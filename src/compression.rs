@@ -0,0 +1,266 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::io;
+
+use async_compression::tokio::bufread::{BzDecoder, GzipDecoder, XzDecoder, ZstdDecoder};
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream, StreamExt};
+use tokio::io::AsyncRead;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::fetcher::{ByteStream, Content, Metadata};
+
+/// Number of leading bytes needed to recognise every supported format.
+const MAGIC_LEN: usize = 6;
+
+/// The compression of a fetched payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// the payload is read as is.
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    /// The compression of a `compression => 'name'` option.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" | "uncompressed" => Some(Compression::None),
+            "gzip" | "gz" => Some(Compression::Gzip),
+            "zstd" | "zst" => Some(Compression::Zstd),
+            "bzip2" | "bz2" => Some(Compression::Bzip2),
+            "xz" => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+
+    /// The compression implied by a `Content-Encoding` header.
+    fn from_encoding(encoding: &str) -> Option<Self> {
+        match encoding.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Compression::Gzip),
+            "zstd" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// The compression implied by the last extension of `file_name`.
+    fn from_file_name(file_name: &str) -> Option<Self> {
+        let (_, extension) = file_name.rsplit_once('.')?;
+        Self::from_name(extension).filter(|c| *c != Compression::None)
+    }
+
+    fn from_magic(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0x1f, 0x8b, ..] => Some(Compression::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Compression::Zstd),
+            [b'B', b'Z', b'h', ..] => Some(Compression::Bzip2),
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+}
+
+/// Decompress `content` while it is streamed. Unless `compression` is given
+/// it is detected from the `Content-Encoding`, the file extension and finally
+/// the magic bytes at the start of the payload.
+///
+/// The compression extension is dropped from the file name of the result, so
+/// `cases.csv.gz` is loaded as `cases.csv`.
+pub async fn decompress(content: Content, compression: Option<Compression>) -> io::Result<Content> {
+    let Content { metadata, body } = content;
+    let detected = compression
+        .or_else(|| {
+            metadata
+                .content_encoding
+                .as_deref()
+                .and_then(Compression::from_encoding)
+        })
+        .or_else(|| {
+            metadata
+                .file_name
+                .as_deref()
+                .and_then(Compression::from_file_name)
+        });
+    let (compression, body) = match detected {
+        Some(compression) => (compression, body),
+        None => {
+            let (head, body) = peek(body).await?;
+            let compression = Compression::from_magic(&head).unwrap_or(Compression::None);
+            (compression, body)
+        }
+    };
+
+    let reader = StreamReader::new(body);
+    let body: ByteStream = match compression {
+        Compression::None => return Ok(Content::new(metadata, Box::pin(reader.into_inner()))),
+        Compression::Gzip => {
+            let mut decoder = GzipDecoder::new(reader);
+            decoder.multiple_members(true);
+            stream_of(decoder)
+        }
+        Compression::Zstd => stream_of(ZstdDecoder::new(reader)),
+        Compression::Bzip2 => stream_of(BzDecoder::new(reader)),
+        Compression::Xz => stream_of(XzDecoder::new(reader)),
+    };
+    let file_name = metadata.file_name.map(|name| {
+        match Compression::from_file_name(&name).and(name.rsplit_once('.')) {
+            Some((stem, _)) => stem.to_owned(),
+            None => name,
+        }
+    });
+    let metadata = Metadata {
+        file_name,
+        content_type: metadata.content_type.filter(|t| !is_compressed_type(t)),
+        content_encoding: None,
+        content_length: None,
+        ..metadata
+    };
+    Ok(Content::new(metadata, body))
+}
+
+fn stream_of(decoder: impl AsyncRead + Send + 'static) -> ByteStream {
+    Box::pin(ReaderStream::new(decoder))
+}
+
+/// Read the first bytes of `body`, returning them along with the whole body.
+async fn peek(mut body: ByteStream) -> io::Result<(Bytes, ByteStream)> {
+    let mut head = BytesMut::new();
+    while head.len() < MAGIC_LEN {
+        match body.next().await {
+            Some(chunk) => head.extend_from_slice(&chunk?),
+            None => break,
+        }
+    }
+    let head = head.freeze();
+    let replay = stream::once(std::future::ready(Ok(head.clone())));
+    Ok((head, Box::pin(replay.chain(body))))
+}
+
+fn is_compressed_type(content_type: &str) -> bool {
+    matches!(
+        content_type,
+        "application/gzip"
+            | "application/x-gzip"
+            | "application/zstd"
+            | "application/x-bzip2"
+            | "application/x-xz"
+            | "application/octet-stream"
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_compression::tokio::write::{GzipEncoder, ZstdEncoder};
+    use tokio::io::AsyncWriteExt;
+
+    use crate::{session::Session, test_util::TempDir};
+
+    const CSV: &[u8] = b"a,b\n1,2\n3,4\n";
+
+    async fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzipEncoder::new(vec![]);
+        encoder.write_all(data).await.unwrap();
+        encoder.shutdown().await.unwrap();
+        encoder.into_inner()
+    }
+
+    fn named(file_name: &str, bytes: Vec<u8>) -> Content {
+        let metadata = Metadata {
+            file_name: Some(file_name.to_owned()),
+            ..Default::default()
+        };
+        Content::from_bytes(metadata, bytes)
+    }
+
+    #[tokio::test]
+    async fn it_detects_the_compression() {
+        let content = decompress(named("cases.csv.gz", gzip(CSV).await), None)
+            .await
+            .unwrap();
+        assert_eq!(content.metadata.file_name.as_deref(), Some("cases.csv"));
+        assert_eq!(content.bytes().await.unwrap(), CSV);
+
+        // by magic bytes
+        let mut encoder = ZstdEncoder::new(vec![]);
+        encoder.write_all(CSV).await.unwrap();
+        encoder.shutdown().await.unwrap();
+        let content = decompress(named("cases", encoder.into_inner()), None)
+            .await
+            .unwrap();
+        assert_eq!(content.bytes().await.unwrap(), CSV);
+
+        let content = decompress(named("cases.csv", CSV.to_vec()), None)
+            .await
+            .unwrap();
+        assert_eq!(content.metadata.content_length, Some(CSV.len() as u64));
+        assert_eq!(content.bytes().await.unwrap(), CSV);
+    }
+
+    #[tokio::test]
+    async fn it_honours_an_explicit_compression() {
+        let content = decompress(named("cases.gz", CSV.to_vec()), Some(Compression::None))
+            .await
+            .unwrap();
+        assert_eq!(content.bytes().await.unwrap(), CSV);
+
+        let content = decompress(named("cases.csv", CSV.to_vec()), Some(Compression::Xz))
+            .await
+            .unwrap();
+        assert!(content.bytes().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn it_reads_compressed_sources() {
+        use async_compression::tokio::write::GzipEncoder;
+        use tokio::io::AsyncWriteExt;
+
+        let dir = TempDir::new("gzip");
+        let mut encoder = GzipEncoder::new(vec![]);
+        encoder.write_all(b"a,b\n1,x\n2,y\n").await.unwrap();
+        encoder.shutdown().await.unwrap();
+        let gzipped = encoder.into_inner();
+        std::fs::write(dir.join("numbers.csv.gz"), &gzipped).unwrap();
+        std::fs::write(dir.join("numbers.bin"), &gzipped).unwrap();
+        std::fs::write(dir.join("plain.gz"), b"a\n1\n").unwrap();
+
+        let mut session = Session::default();
+        let sql = format!(
+            "select a from file://{}/numbers.csv.gz where a > 1",
+            dir.display()
+        );
+        assert_eq!(session.query(sql).await.unwrap().shape(), (1, 1));
+        let sql = format!(
+            "select b from read_csv('file://{}/numbers.bin', compression => 'gzip')",
+            dir.display()
+        );
+        assert_eq!(session.query(sql).await.unwrap().shape(), (2, 1));
+        let sql = format!(
+            "select a from read_csv('file://{}/plain.gz', compression => 'none')",
+            dir.display()
+        );
+        assert_eq!(session.query(sql).await.unwrap().shape(), (1, 1));
+    }
+}
@@ -19,10 +19,24 @@
 // LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
-use anyhow::{anyhow, Error, Ok};
+use std::ops::ControlFlow;
+
 use polars::prelude::*;
 use sqlparser::ast::{
-    Expr as SqlExpr, Offset as SqlOffset, OrderByExpr, Select, SelectItem, SetExpr, Statement, TableFactor, TableWithJoins, Value
+    BinaryOperator, CopyOption, CopySource, CopyTarget, DataType as SqlDataType, Expr as SqlExpr,
+    Function as SqlFunction, FunctionArg, FunctionArgExpr, FunctionArguments, GroupByExpr,
+    MacroDefinition, ObjectName, ObjectType, Offset as SqlOffset, OrderByExpr, Select, SelectItem,
+    SetExpr, Statement, TableFactor, TableWithJoins, TimezoneInfo, UnaryOperator, Value, Visit,
+    Visitor, WindowType,
+};
+
+use crate::{
+    compression::Compression,
+    error::Error,
+    loader::ReadOptions,
+    macros::Macro,
+    udf::FunctionRegistry,
+    writer::{FileFormat, WriteOptions},
 };
 
 #[derive(Debug, Default)]
@@ -30,52 +44,410 @@ pub struct Sql<'a> {
     pub(crate) selection: Vec<Expr>,
     pub(crate) condition: Option<Expr>,
     pub(crate) source: &'a str,
-    pub(crate) order_by: Vec<(&'a str, bool)>,
+    /// set by the reader function wrapping the source, if any.
+    pub(crate) read_options: ReadOptions,
+    /// the GROUP BY keys, the selection is aggregated per group when not empty.
+    pub(crate) group_by: Vec<Expr>,
+    pub(crate) having: Option<Expr>,
+    /// column name and whether it is sorted descending.
+    pub(crate) order_by: Vec<(String, bool)>,
     pub(crate) offset: Option<i64>,
     pub(crate) limit: Option<usize>,
 }
 
-// TODO: improving the rest of Sql attributes.
+/// Whether a catalog entry is a (materialised) table or a view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableKind {
+    Table,
+    View,
+}
+
+/// A statement the session executes, either a query or a change of its catalog.
 #[derive(Debug)]
-pub struct Offset(pub(crate) SqlOffset);
+pub enum Command {
+    Query(Statement),
+    /// `CREATE [OR REPLACE] [TEMP] VIEW name AS query`
+    CreateView {
+        name: String,
+        query: Statement,
+        or_replace: bool,
+        if_not_exists: bool,
+    },
+    /// `CREATE [OR REPLACE] [TEMP] TABLE name AS query`
+    CreateTable {
+        name: String,
+        query: Statement,
+        or_replace: bool,
+        if_not_exists: bool,
+    },
+    /// `COPY (query) TO 'target' [(FORMAT name, DELIMITER 'c', HEADER b)]`
+    Copy {
+        query: Statement,
+        target: String,
+        options: WriteOptions,
+    },
+    /// `INSERT INTO 'target' query`, appending to the target.
+    Insert {
+        query: Statement,
+        target: String,
+        options: WriteOptions,
+    },
+    /// `EXPLAIN [ANALYZE] query`
+    Explain {
+        query: Statement,
+        analyze: bool,
+    },
+    /// `SHOW TABLES`
+    ShowTables,
+    /// `DESCRIBE source` or `SHOW COLUMNS FROM source`
+    Describe {
+        source: String,
+    },
+    /// `DROP TABLE|VIEW [IF EXISTS] name, ...`
+    Drop {
+        kind: TableKind,
+        names: Vec<String>,
+        if_exists: bool,
+    },
+    /// `SET name = value`, referenced as `${name}` by later statements.
+    SetVariable {
+        name: String,
+        value: SqlExpr,
+    },
+    /// `CREATE [OR REPLACE] [TEMP] MACRO name(args) AS expr`
+    CreateMacro {
+        name: String,
+        definition: Macro,
+        or_replace: bool,
+    },
+}
+
+#[derive(Debug)]
+pub struct Offset<'a>(pub(crate) &'a SqlOffset);
 #[derive(Debug)]
 pub struct Source<'a>(pub(crate) &'a [TableWithJoins]);
 #[derive(Debug)]
 pub struct OrderBy<'a>(pub(crate) &'a OrderByExpr);
 #[derive(Debug)]
-pub struct Limit(pub(crate) SqlExpr);
+pub struct Limit<'a>(pub(crate) &'a SqlExpr);
+#[derive(Debug)]
+pub struct Condition<'a>(
+    pub(crate) Option<&'a SqlExpr>,
+    pub(crate) &'a FunctionRegistry,
+);
+#[derive(Debug)]
+pub struct Projection<'a>(pub(crate) &'a SelectItem, pub(crate) &'a FunctionRegistry);
 #[derive(Debug)]
-pub struct Condition(pub(crate) Option<SqlExpr>);
+pub struct Expression<'a>(pub(crate) &'a SqlExpr, pub(crate) &'a FunctionRegistry);
 #[derive(Debug)]
-pub struct Projection(pub(crate) SelectItem);
+pub struct Literal<'a>(pub(crate) &'a Value);
+#[derive(Debug)]
+pub struct Function<'a>(pub(crate) &'a SqlFunction, pub(crate) &'a FunctionRegistry);
+#[derive(Debug)]
+pub struct SqlType<'a>(pub(crate) &'a SqlDataType);
 
 impl<'a> TryFrom<&'a Statement> for Sql<'a> {
     type Error = Error;
 
     fn try_from(sql: &'a Statement) -> Result<Self, Self::Error> {
+        Sql::translate(sql, &FunctionRegistry::default())
+    }
+}
+
+impl<'a> Sql<'a> {
+    /// Translate a query, resolving calls to user defined `functions`.
+    pub fn translate(sql: &'a Statement, functions: &FunctionRegistry) -> Result<Self, Error> {
         match sql {
             Statement::Query(q) => {
                 let Select {
                     from: table_with_joins,
                     selection: where_clause,
                     projection,
-
-                    group_by: _,
+                    group_by,
+                    having,
+                    distinct,
                     ..
                 } = match &*q.body {
                     SetExpr::Select(statement) => statement.as_ref(),
-                    _ => return Err(anyhow!("We only support Select Query at the moment")),
+                    body => return Err(Error::unsupported("non select query", body)),
+                };
+
+                let group_by = match group_by {
+                    GroupByExpr::Expressions(exprs) => exprs,
+                    GroupByExpr::All => return Err(Error::unsupported("GROUP BY ALL", group_by)),
                 };
-                let source = table_with_joins;
-                Ok(Sql::default())
+                if let Some(having) = having.as_ref().filter(|_| group_by.is_empty()) {
+                    return Err(Error::unsupported("HAVING without GROUP BY", having));
+                }
+                if let Some(distinct) = distinct {
+                    return Err(Error::unsupported("DISTINCT", distinct));
+                }
+
+                let (source, read_options) = Source(table_with_joins).try_into()?;
+                let condition = Condition(where_clause.as_ref(), functions).try_into()?;
+                let selection = projection
+                    .iter()
+                    .map(|item| Projection(item, functions).try_into())
+                    .collect::<Result<Vec<_>, _>>()?;
+                ensure_grouped(projection, group_by, functions)?;
+                let group_by = group_by
+                    .iter()
+                    .map(|expr| Expression(expr, functions).try_into())
+                    .collect::<Result<Vec<_>, _>>()?;
+                let having = Condition(having.as_ref(), functions).try_into()?;
+                let order_by = q
+                    .order_by
+                    .iter()
+                    .map(|expr| OrderBy(expr).try_into())
+                    .collect::<Result<Vec<_>, _>>()?;
+                let offset = q.offset.as_ref().map(|o| Offset(o).into());
+                let limit = q.limit.as_ref().map(|l| Limit(l).try_into()).transpose()?;
+
+                Ok(Sql {
+                    selection,
+                    condition,
+                    source,
+                    read_options,
+                    group_by,
+                    having,
+                    order_by,
+                    offset,
+                    limit,
+                })
+            }
+            statement => Err(Error::unsupported("non query statement", statement)),
+        }
+    }
+}
+
+impl TryFrom<Statement> for Command {
+    type Error = Error;
+
+    fn try_from(statement: Statement) -> Result<Self, Self::Error> {
+        match statement {
+            Statement::Query(_) => Ok(Command::Query(statement)),
+            Statement::CreateView {
+                materialized: false,
+                ref name,
+                ref columns,
+                ref query,
+                or_replace,
+                if_not_exists,
+                ..
+            } if columns.is_empty() => Ok(Command::CreateView {
+                name: object_name(name)?,
+                query: Statement::Query(query.clone()),
+                or_replace,
+                if_not_exists,
+            }),
+            Statement::CreateTable {
+                ref name,
+                ref columns,
+                query: Some(ref query),
+                or_replace,
+                if_not_exists,
+                ..
+            } if columns.is_empty() => Ok(Command::CreateTable {
+                name: object_name(name)?,
+                query: Statement::Query(query.clone()),
+                or_replace,
+                if_not_exists,
+            }),
+            Statement::Drop {
+                object_type: object_type @ (ObjectType::Table | ObjectType::View),
+                ref names,
+                if_exists,
+                ..
+            } => Ok(Command::Drop {
+                kind: match object_type {
+                    ObjectType::View => TableKind::View,
+                    _ => TableKind::Table,
+                },
+                names: names.iter().map(object_name).collect::<Result<_, _>>()?,
+                if_exists,
+            }),
+            Statement::Copy {
+                source: CopySource::Query(ref query),
+                to: true,
+                target: CopyTarget::File { ref filename },
+                ref options,
+                ref legacy_options,
+                ..
+            } if legacy_options.is_empty() => Ok(Command::Copy {
+                query: Statement::Query(query.clone()),
+                target: filename.clone(),
+                options: write_options(options)?,
+            }),
+            Statement::Insert(ref insert)
+                if insert.columns.is_empty()
+                    && insert.on.is_none()
+                    && insert.returning.is_none() =>
+            {
+                match &insert.source {
+                    Some(query) => Ok(Command::Insert {
+                        query: Statement::Query(query.clone()),
+                        target: object_name(&insert.table_name)?,
+                        options: WriteOptions {
+                            append: true,
+                            ..Default::default()
+                        },
+                    }),
+                    None => Err(Error::unsupported("INSERT without a query", statement)),
+                }
+            }
+            Statement::Explain {
+                statement: query,
+                analyze,
+                format: None,
+                ..
+            } if matches!(*query, Statement::Query(_)) => Ok(Command::Explain {
+                query: *query,
+                analyze,
+            }),
+            Statement::ShowTables {
+                filter: None,
+                db_name: None,
+                ..
+            } => Ok(Command::ShowTables),
+            Statement::ExplainTable { ref table_name, .. }
+            | Statement::ShowColumns {
+                ref table_name,
+                filter: None,
+                ..
+            } => Ok(Command::Describe {
+                source: object_name(table_name)?,
+            }),
+            Statement::SetVariable {
+                local: false,
+                hivevar: false,
+                ref variable,
+                ref value,
+            } => match value.as_slice() {
+                [value] => Ok(Command::SetVariable {
+                    name: object_name(variable)?.to_lowercase(),
+                    value: value.clone(),
+                }),
+                _ => Err(Error::unsupported("SET of multiple values", statement)),
+            },
+            Statement::CreateMacro {
+                ref name,
+                ref args,
+                definition: MacroDefinition::Expr(ref body),
+                or_replace,
+                ..
+            } => Ok(Command::CreateMacro {
+                name: object_name(name)?.to_lowercase(),
+                definition: Macro::new(args.clone().unwrap_or_default(), body.clone()),
+                or_replace,
+            }),
+            statement => Err(Error::unsupported("statement", statement)),
+        }
+    }
+}
+
+/// The built-in aggregate functions, see `call`.
+const AGGREGATES: [&str; 6] = ["count", "sum", "avg", "mean", "min", "max"];
+
+/// Columns selected by a grouped query must be GROUP BY keys or sit inside an aggregate.
+fn ensure_grouped(
+    projection: &[SelectItem],
+    group_by: &[SqlExpr],
+    functions: &FunctionRegistry,
+) -> Result<(), Error> {
+    if group_by.is_empty() {
+        return Ok(());
+    }
+    for item in projection {
+        let expr = match item {
+            SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => expr,
+            SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(..) => {
+                return Err(Error::unsupported("wildcard with GROUP BY", item))
             }
-            _ => Ok(Sql::default()),
+        };
+        let mut grouped = Grouped {
+            group_by,
+            functions,
+            depth: 0,
+        };
+        if let ControlFlow::Break(column) = expr.visit(&mut grouped) {
+            return Err(Error::unsupported("column outside of GROUP BY", column));
         }
     }
+    Ok(())
 }
 
-impl<'a> From<Offset> for i64 {
-    fn from(offset: Offset) -> Self {
+/// Finds a column that is neither a GROUP BY key nor inside one or an aggregate.
+struct Grouped<'a> {
+    group_by: &'a [SqlExpr],
+    functions: &'a FunctionRegistry,
+    /// how many enclosing keys or aggregates the visited expression sits in.
+    depth: usize,
+}
+
+impl Grouped<'_> {
+    fn covers(&self, expr: &SqlExpr) -> bool {
+        match expr {
+            SqlExpr::Function(f) => {
+                let name = f.name.to_string().to_lowercase();
+                AGGREGATES.contains(&name.as_str()) || self.functions.aggregate(&name).is_some()
+            }
+            expr => self.group_by.contains(expr),
+        }
+    }
+}
+
+impl Visitor for Grouped<'_> {
+    type Break = SqlExpr;
+
+    fn pre_visit_expr(&mut self, expr: &SqlExpr) -> ControlFlow<Self::Break> {
+        if self.covers(expr) {
+            self.depth += 1;
+        } else if self.depth == 0
+            && matches!(
+                expr,
+                SqlExpr::Identifier(_) | SqlExpr::CompoundIdentifier(_)
+            )
+        {
+            return ControlFlow::Break(expr.clone());
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_expr(&mut self, expr: &SqlExpr) -> ControlFlow<Self::Break> {
+        if self.covers(expr) {
+            self.depth -= 1;
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+fn write_options(options: &[CopyOption]) -> Result<WriteOptions, Error> {
+    let mut write = WriteOptions::default();
+    for option in options {
+        match option {
+            CopyOption::Format(name) => {
+                write.format = Some(
+                    FileFormat::from_name(&name.value)
+                        .ok_or_else(|| Error::unsupported("COPY format", name))?,
+                );
+            }
+            CopyOption::Delimiter(c) if c.is_ascii() => write.delimiter = Some(*c as u8),
+            CopyOption::Header(header) => write.header = Some(*header),
+            option => return Err(Error::unsupported("COPY option", option)),
+        }
+    }
+    Ok(write)
+}
+
+fn object_name(name: &ObjectName) -> Result<String, Error> {
+    match name.0.as_slice() {
+        [ident] => Ok(ident.value.clone()),
+        _ => Err(Error::unsupported("qualified table name", name)),
+    }
+}
+
+impl<'a> From<Offset<'a>> for i64 {
+    fn from(offset: Offset<'a>) -> Self {
         match offset.0 {
             SqlOffset {
                 value: SqlExpr::Value(Value::Number(v, _)),
@@ -86,43 +458,405 @@ impl<'a> From<Offset> for i64 {
     }
 }
 
-impl<'a> TryFrom<Source<'a>> for &'a str {
+impl<'a> TryFrom<Limit<'a>> for usize {
     type Error = Error;
-    fn try_from(source: Source<'a>) -> Result<Self, Self::Error> {
-        if source.0.len() != 1 {
-            return Err(anyhow!(
-                "we only support one single data source at the moment"
-            ));
+    fn try_from(limit: Limit<'a>) -> Result<Self, Self::Error> {
+        match limit.0 {
+            SqlExpr::Value(Value::Number(v, _)) => v
+                .parse()
+                .map_err(|_| Error::unsupported("non integer LIMIT", limit.0)),
+            expr => Err(Error::unsupported("non literal LIMIT", expr)),
         }
+    }
+}
 
-        if source.0.len() == 0 {
-            return Err(anyhow!("there is no data source"));
-        }
+impl<'a> TryFrom<Source<'a>> for (&'a str, ReadOptions) {
+    type Error = Error;
+    fn try_from(source: Source<'a>) -> Result<Self, Self::Error> {
+        let table = match source.0 {
+            [] => return Err(Error::unsupported("query without data source", "")),
+            [table] => table,
+            [_, second, ..] => return Err(Error::unsupported("multiple data sources", second)),
+        };
 
-        let table = source.0.first().unwrap();
-        if !table.joins.is_empty() {
-            return Err(anyhow!("We do not support joint data source at the moment"));
+        if let Some(join) = table.joins.first() {
+            return Err(Error::unsupported("joined data sources", &join.relation));
         }
         match &table.relation {
-            TableFactor::Table { name, .. } => Ok(&name.0.first().unwrap().value),
-            _ => Err(anyhow!("we only support table new")),
+            TableFactor::Table {
+                name, args: None, ..
+            } => match name.0.as_slice() {
+                [ident] => Ok((&ident.value, ReadOptions::default())),
+                _ => Err(Error::unsupported("qualified table name", name)),
+            },
+            TableFactor::Table {
+                name,
+                args: Some(args),
+                ..
+            } => read_function(name, args),
+            relation => Err(Error::unsupported("non table data source", relation)),
+        }
+    }
+}
+
+/// `read_csv|read_json|read_ndjson|read_parquet(url [, compression => name])`
+fn read_function<'a>(
+    name: &ObjectName,
+    args: &'a [FunctionArg],
+) -> Result<(&'a str, ReadOptions), Error> {
+    let format = match name.to_string().to_lowercase().as_str() {
+        "read_csv" => FileFormat::Csv,
+        "read_json" | "read_ndjson" => FileFormat::NdJson,
+        "read_parquet" => FileFormat::Parquet,
+        _ => return Err(Error::unsupported("table function", name)),
+    };
+    let mut options = ReadOptions {
+        format: Some(format),
+        ..Default::default()
+    };
+    let (source, named) = match args {
+        [FunctionArg::Unnamed(FunctionArgExpr::Expr(source)), named @ ..] => (source, named),
+        _ => return Err(Error::unsupported(format!("arguments of {name}"), name)),
+    };
+    for arg in named {
+        match arg {
+            FunctionArg::Named {
+                name,
+                arg: FunctionArgExpr::Expr(value),
+                ..
+            } if name.value.eq_ignore_ascii_case("compression") => {
+                let compression = option_value(value).and_then(Compression::from_name);
+                options.compression =
+                    Some(compression.ok_or_else(|| Error::unsupported("compression", value))?);
+            }
+            arg => return Err(Error::unsupported("reader option", arg)),
         }
     }
+    let source = option_value(source).ok_or_else(|| Error::unsupported("data source", source))?;
+    Ok((source, options))
+}
+
+/// The text of a string literal or bare identifier.
+fn option_value(expr: &SqlExpr) -> Option<&str> {
+    match expr {
+        SqlExpr::Value(Value::SingleQuotedString(s) | Value::DoubleQuotedString(s)) => Some(s),
+        SqlExpr::Identifier(id) => Some(&id.value),
+        _ => None,
+    }
 }
 
 impl<'a> TryFrom<OrderBy<'a>> for (String, bool) {
     type Error = Error;
     fn try_from(order_by: OrderBy<'a>) -> Result<Self, Self::Error> {
         let name = match &order_by.0.expr {
-            SqlExpr::Identifier(id) => id.to_string(),
-            expr => {
-                return Err(anyhow!(
-                    "we only support inentifier for order by, got {}",
-                    expr
-                ))
-            }
+            SqlExpr::Identifier(id) => id.value.clone(),
+            expr => return Err(Error::unsupported("ORDER BY a non identifier", expr)),
         };
-        let asc = order_by.0.asc.unwrap_or(true);
-        Ok((name, asc))
+        let desc = !order_by.0.asc.unwrap_or(true);
+        Ok((name, desc))
+    }
+}
+
+impl<'a> TryFrom<Condition<'a>> for Option<Expr> {
+    type Error = Error;
+    fn try_from(condition: Condition<'a>) -> Result<Self, Self::Error> {
+        condition
+            .0
+            .map(|expr| Expression(expr, condition.1).try_into())
+            .transpose()
+    }
+}
+
+impl<'a> TryFrom<Projection<'a>> for Expr {
+    type Error = Error;
+    fn try_from(projection: Projection<'a>) -> Result<Self, Self::Error> {
+        match projection.0 {
+            SelectItem::UnnamedExpr(expr) => Expression(expr, projection.1).try_into(),
+            SelectItem::ExprWithAlias { expr, alias } => {
+                Ok(Expr::try_from(Expression(expr, projection.1))?.alias(&alias.value))
+            }
+            SelectItem::Wildcard(_) => Ok(col("*")),
+            item => Err(Error::unsupported("qualified wildcard", item)),
+        }
+    }
+}
+
+impl<'a> TryFrom<Expression<'a>> for Expr {
+    type Error = Error;
+    fn try_from(expression: Expression<'a>) -> Result<Self, Self::Error> {
+        let functions = expression.1;
+        let expr = |e: &SqlExpr| Expr::try_from(Expression(e, functions));
+        match expression.0 {
+            SqlExpr::Identifier(id) => Ok(col(&id.value)),
+            SqlExpr::Value(v) => Literal(v).try_into(),
+            SqlExpr::Nested(e) => expr(e),
+            SqlExpr::IsNull(e) => Ok(expr(e)?.is_null()),
+            SqlExpr::IsNotNull(e) => Ok(expr(e)?.is_not_null()),
+            SqlExpr::Between {
+                expr: e,
+                negated,
+                low,
+                high,
+            } => {
+                let e = expr(e)?;
+                let between = e.clone().gt_eq(expr(low)?).and(e.lt_eq(expr(high)?));
+                Ok(if *negated { between.not() } else { between })
+            }
+            SqlExpr::InList {
+                expr: e,
+                list,
+                negated,
+            } => {
+                let e = expr(e)?;
+                let any = list.iter().try_fold(lit(false), |acc, item| {
+                    Ok::<_, Error>(acc.or(e.clone().eq(expr(item)?)))
+                })?;
+                Ok(if *negated { any.not() } else { any })
+            }
+            SqlExpr::UnaryOp { op, expr: e } => match op {
+                UnaryOperator::Not => Ok(expr(e)?.not()),
+                UnaryOperator::Minus => Ok(lit(0) - expr(e)?),
+                UnaryOperator::Plus => expr(e),
+                _ => Err(Error::unsupported(
+                    format!("unary operator {op}"),
+                    expression.0,
+                )),
+            },
+            SqlExpr::Cast {
+                expr: e,
+                data_type,
+                format: None,
+                ..
+            } => Ok(expr(e)?.cast(SqlType(data_type).try_into()?)),
+            SqlExpr::TypedString { data_type, value } => {
+                Ok(lit(value.clone()).cast(SqlType(data_type).try_into()?))
+            }
+            SqlExpr::Function(f) => Function(f, functions).try_into(),
+            SqlExpr::BinaryOp { left, op, right } => {
+                let (l, r) = (expr(left)?, expr(right)?);
+                match op {
+                    BinaryOperator::Plus => Ok(l + r),
+                    BinaryOperator::Minus => Ok(l - r),
+                    BinaryOperator::Multiply => Ok(l * r),
+                    BinaryOperator::Divide => Ok(l / r),
+                    BinaryOperator::Modulo => Ok(l % r),
+                    BinaryOperator::Gt => Ok(l.gt(r)),
+                    BinaryOperator::Lt => Ok(l.lt(r)),
+                    BinaryOperator::GtEq => Ok(l.gt_eq(r)),
+                    BinaryOperator::LtEq => Ok(l.lt_eq(r)),
+                    BinaryOperator::Eq => Ok(l.eq(r)),
+                    BinaryOperator::NotEq => Ok(l.neq(r)),
+                    BinaryOperator::And => Ok(l.and(r)),
+                    BinaryOperator::Or => Ok(l.or(r)),
+                    _ => Err(Error::unsupported(
+                        format!("binary operator {op}"),
+                        expression.0,
+                    )),
+                }
+            }
+            e => Err(Error::unsupported("expression", e)),
+        }
+    }
+}
+
+impl<'a> TryFrom<Literal<'a>> for Expr {
+    type Error = Error;
+    fn try_from(literal: Literal<'a>) -> Result<Self, Self::Error> {
+        match literal.0 {
+            Value::Number(v, _) => v
+                .parse::<i64>()
+                .map(lit)
+                .or_else(|_| v.parse::<f64>().map(lit))
+                .map_err(|_| Error::unsupported("numeric literal", v)),
+            Value::SingleQuotedString(s) | Value::DoubleQuotedString(s) => Ok(lit(s.clone())),
+            Value::Boolean(b) => Ok(lit(*b)),
+            Value::Null => Ok(lit(NULL)),
+            v => Err(Error::unsupported("literal", v)),
+        }
+    }
+}
+
+impl<'a> TryFrom<Function<'a>> for Expr {
+    type Error = Error;
+    fn try_from(function: Function<'a>) -> Result<Self, Self::Error> {
+        let f = function.0;
+        if f.filter.is_some() || !f.within_group.is_empty() {
+            return Err(Error::unsupported("filtered function", f));
+        }
+        let expr = call(f, function.1)?;
+        match &f.over {
+            None => Ok(expr),
+            Some(WindowType::WindowSpec(spec))
+                if spec.window_name.is_none()
+                    && spec.order_by.is_empty()
+                    && spec.window_frame.is_none() =>
+            {
+                let partition_by = spec
+                    .partition_by
+                    .iter()
+                    .map(|e| Expr::try_from(Expression(e, function.1)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match partition_by.is_empty() {
+                    true => Ok(expr),
+                    false => Ok(expr.over(partition_by)),
+                }
+            }
+            Some(window) => Err(Error::unsupported("ordered or named window", window)),
+        }
+    }
+}
+
+/// A function call, without its window.
+fn call(f: &SqlFunction, functions: &FunctionRegistry) -> Result<Expr, Error> {
+    let name = f.name.to_string().to_lowercase();
+    let args = function_args(f)?;
+    let arg = |i: usize| match args.get(i) {
+        Some(FunctionArgExpr::Expr(e)) => Expr::try_from(Expression(e, functions)),
+        _ => Err(Error::unsupported(format!("arguments of {name}"), f)),
+    };
+    if let Some(udf) = functions.scalar(&name) {
+        let args = (0..args.len()).map(arg).collect::<Result<_, _>>()?;
+        return Ok(udf.call(args));
+    }
+    if let Some(udaf) = functions.aggregate(&name) {
+        let args = (0..args.len()).map(arg).collect::<Result<_, _>>()?;
+        return Ok(udaf.call(args));
+    }
+    let expr = match (name.as_str(), args.as_slice()) {
+        ("count", [FunctionArgExpr::Wildcard]) => len(),
+        ("count", [_]) => arg(0)?.count(),
+        ("sum", [_]) => arg(0)?.sum(),
+        ("avg" | "mean", [_]) => arg(0)?.mean(),
+        ("min", [_]) => arg(0)?.min(),
+        ("max", [_]) => arg(0)?.max(),
+        ("lower", [_]) => arg(0)?.str().to_lowercase(),
+        ("upper", [_]) => arg(0)?.str().to_uppercase(),
+        ("length" | "char_length", [_]) => arg(0)?.str().len_chars(),
+        _ => return Err(Error::unsupported(format!("function {name}"), f)),
+    };
+    Ok(expr)
+}
+
+/// The plain, unnamed arguments of a function call.
+pub(crate) fn function_args(f: &SqlFunction) -> Result<Vec<&FunctionArgExpr>, Error> {
+    match &f.args {
+        FunctionArguments::None => Ok(vec![]),
+        FunctionArguments::List(list)
+            if list.duplicate_treatment.is_none() && list.clauses.is_empty() =>
+        {
+            list.args
+                .iter()
+                .map(|arg| match arg {
+                    FunctionArg::Unnamed(arg) => Ok(arg),
+                    arg => Err(Error::unsupported("named function argument", arg)),
+                })
+                .collect()
+        }
+        _ => Err(Error::unsupported("function argument clause", f)),
+    }
+}
+
+impl<'a> TryFrom<SqlType<'a>> for DataType {
+    type Error = Error;
+    fn try_from(data_type: SqlType<'a>) -> Result<Self, Self::Error> {
+        Ok(match data_type.0 {
+            SqlDataType::Boolean | SqlDataType::Bool => DataType::Boolean,
+            SqlDataType::Int(_)
+            | SqlDataType::Integer(_)
+            | SqlDataType::BigInt(_)
+            | SqlDataType::Int64 => DataType::Int64,
+            SqlDataType::Float(_)
+            | SqlDataType::Real
+            | SqlDataType::Double
+            | SqlDataType::DoublePrecision
+            | SqlDataType::Float64
+            | SqlDataType::Decimal(_)
+            | SqlDataType::Numeric(_) => DataType::Float64,
+            SqlDataType::Varchar(_)
+            | SqlDataType::Char(_)
+            | SqlDataType::Text
+            | SqlDataType::String(_) => DataType::String,
+            SqlDataType::Date => DataType::Date,
+            SqlDataType::Timestamp(_, TimezoneInfo::None) | SqlDataType::Datetime(_) => {
+                DataType::Datetime(TimeUnit::Microseconds, None)
+            }
+            t => return Err(Error::unsupported("data type", t)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dialect::OrinDialect;
+    use sqlparser::parser::Parser;
+
+    fn parse(sql: &str) -> Statement {
+        Parser::parse_sql(&OrinDialect, sql).unwrap().remove(0)
+    }
+
+    #[test]
+    fn it_translates_a_simple_query() {
+        let statement = parse(
+            "select location name, new_cases from file://data.csv \
+            where new_deaths > 500 order by new_cases desc limit 6 offset 5",
+        );
+        let sql = Sql::try_from(&statement).unwrap();
+        assert_eq!(sql.source, "file://data.csv");
+        assert_eq!(sql.selection.len(), 2);
+        assert!(sql.condition.is_some());
+        assert_eq!(sql.order_by, vec![("new_cases".to_owned(), true)]);
+        assert_eq!(sql.offset, Some(5));
+        assert_eq!(sql.limit, Some(6));
+    }
+
+    #[test]
+    fn it_translates_reader_functions() {
+        let statement =
+            parse("select a from read_csv('file:///data/cases.csv.bin', compression => 'gzip')");
+        let sql = Sql::try_from(&statement).unwrap();
+        assert_eq!(sql.source, "file:///data/cases.csv.bin");
+        assert_eq!(sql.read_options.format, Some(FileFormat::Csv));
+        assert_eq!(sql.read_options.compression, Some(Compression::Gzip));
+
+        let statement = parse("select a from read_csv('a.csv', compression => 'lz4')");
+        assert!(Sql::try_from(&statement).is_err());
+    }
+
+    #[test]
+    fn it_reports_unsupported_features() {
+        let statement = parse("select distinct a from t");
+        let err = Sql::try_from(&statement).unwrap_err();
+        assert!(
+            matches!(err, Error::UnsupportedFeature { ref feature, .. } if feature == "DISTINCT")
+        );
+    }
+
+    #[test]
+    fn it_translates_grouped_queries() {
+        let statement = parse(
+            "select continent, sum(new_cases) cases, max(new_cases) over (partition by continent) \
+            from t group by continent having count(*) > 2",
+        );
+        let sql = Sql::try_from(&statement).unwrap();
+        assert_eq!(sql.group_by, vec![col("continent")]);
+        assert!(sql.having.is_some());
+
+        let statement = parse("select location, sum(new_cases) from t group by continent");
+        let err = Sql::try_from(&statement).unwrap_err();
+        assert_eq!(err.snippet(), Some("location"));
+    }
+
+    #[test]
+    fn it_rejects_expressions_outside_of_group_by() {
+        let statement = parse("select a + 1, upper(b) from t group by b");
+        let err = Sql::try_from(&statement).unwrap_err();
+        assert_eq!(err.snippet(), Some("a"));
+
+        let statement = parse("select a + 1 x, upper(b), sum(a + c) * 2 from t group by a, b");
+        assert!(Sql::try_from(&statement).is_ok());
+
+        let statement = parse("select (a + 1) * 2 from t group by a + 1");
+        assert!(Sql::try_from(&statement).is_ok());
     }
 }
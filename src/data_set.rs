@@ -20,14 +20,21 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use std::ops::{Deref, DerefMut};
+use std::{
+    ops::{Deref, DerefMut},
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+};
 
-use anyhow::{anyhow, Ok, Result};
 use polars::prelude::*;
-use sqlparser::{ast::Expr, parser::Parser};
-use tracing::info;
 
-use crate::{convert::Sql, dialect::OrinDialect, fetcher::retrieve_data, loader::detect_content};
+use crate::{
+    error::{Error, Result},
+    frame_cache::FrameCache,
+    loader::ReadOptions,
+    session::Session,
+    typecheck::Coercion,
+};
 
 #[derive(Debug)]
 pub struct DataSet(pub DataFrame);
@@ -47,53 +54,202 @@ impl DerefMut for DataSet {
 }
 
 impl DataSet {
-    fn to_csv(&mut self) -> Result<String> {
+    pub fn to_csv(&self) -> Result<String> {
         let mut buf = vec![];
-        let mut writer = CsvWriter::new(&mut buf);
-        writer.finish(self);
-        Ok(String::from_utf8(buf)?)
+        CsvWriter::new(&mut buf).finish(&mut self.0.clone())?;
+        String::from_utf8(buf).map_err(Error::load)
     }
+
+    /// One row per column: its name, polars data type, number of nulls and
+    /// the first non null value.
+    pub fn describe_schema(&self) -> Result<DataSet> {
+        let columns = self.get_columns();
+        let names: Vec<_> = columns.iter().map(|s| s.name()).collect();
+        let types: Vec<_> = columns.iter().map(|s| s.dtype().to_string()).collect();
+        let nulls: Vec<_> = columns.iter().map(|s| s.null_count() as u64).collect();
+        let samples = columns
+            .iter()
+            .map(|s| {
+                let sample = s.drop_nulls().head(Some(1)).cast(&DataType::String)?;
+                let sample = sample.str()?.into_iter().next().flatten();
+                Ok(sample.map(str::to_owned))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(DataSet(df!(
+            "column_name" => names,
+            "data_type" => types,
+            "null_count" => nulls,
+            "sample" => samples,
+        )?))
+    }
+}
+
+/// What `run_script` does when a statement fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnError {
+    /// skip the remaining statements, the failure is the last result.
+    #[default]
+    Stop,
+    /// keep executing the remaining statements.
+    Continue,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryOptions {
+    /// implicit coercions applied while type checking the query.
+    pub coercion: Coercion,
+    /// only used by `run_script`.
+    pub on_error: OnError,
+    /// how many files of a glob, directory or partitioned source are fetched
+    /// at once. A query reads from a single table, since joins, unions and
+    /// subqueries are not supported, so these are the files of that table,
+    /// each fetched once.
+    pub max_concurrent_fetches: usize,
+    /// what relative paths are resolved against, the current directory when
+    /// `None`.
+    pub base_dir: Option<PathBuf>,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        QueryOptions {
+            coercion: Coercion::default(),
+            on_error: OnError::default(),
+            max_concurrent_fetches: 8,
+            base_dir: None,
+        }
+    }
+}
+
+/// The cache of parsed files shared by the free functions of this module,
+/// e.g. to change its budget or invalidate a source. A [`Session`] has a
+/// cache of its own unless given this one with [`Session::with_frame_cache`].
+pub fn shared_frame_cache() -> Arc<Mutex<FrameCache>> {
+    static FRAMES: OnceLock<Arc<Mutex<FrameCache>>> = OnceLock::new();
+    FRAMES.get_or_init(Arc::default).clone()
+}
+
+/// The session a free function runs in: its tables are its own, but the
+/// files parsed by earlier calls are reused.
+fn session(options: &QueryOptions) -> Session {
+    Session::new(options.clone()).with_frame_cache(shared_frame_cache())
 }
 
 pub async fn query<T: AsRef<str>>(sql: T) -> Result<DataSet> {
-    println!("{}", sql.as_ref());
-    let ast = Parser::parse_sql(&OrinDialect::default(), sql.as_ref())?;
-    println!("{:#?}", ast);
-    if ast.len() != 1 {
-        Err::<T, anyhow::Error>(anyhow!("only support single sql at the monment"));
+    query_with_options(sql, &QueryOptions::default()).await
+}
+
+pub async fn query_with_options<T: AsRef<str>>(sql: T, options: &QueryOptions) -> Result<DataSet> {
+    session(options).query(sql).await
+}
+
+/// Run `sql` over `df`, registered as the table `name`.
+pub async fn query_dataframe<T: AsRef<str>>(sql: T, name: &str, df: DataFrame) -> Result<DataSet> {
+    let mut session = session(&QueryOptions::default());
+    session.register_dataframe(name, df);
+    session.query(sql).await
+}
+
+/// Run `sql` over in memory csv, json or parquet `content`, e.g. a `String`,
+/// a `Vec<u8>` or a borrowed `&[u8]`, registered as the table `name`.
+pub async fn query_content<T: AsRef<str>>(
+    sql: T,
+    name: &str,
+    content: impl AsRef<[u8]>,
+) -> Result<DataSet> {
+    let mut session = session(&QueryOptions::default());
+    session.register_content(name, content, ReadOptions::default());
+    session.query(sql).await
+}
+
+/// Execute the semicolon separated statements of `sql` in order, returning
+/// one result per executed statement. See [`Session::run_script`].
+pub async fn run_script<T: AsRef<str>>(
+    sql: T,
+    options: &QueryOptions,
+) -> Result<Vec<Result<DataSet>>> {
+    session(options).run_script(sql).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{covid, TempDir};
+
+    #[tokio::test]
+    async fn it_runs_every_statement_of_a_script() {
+        let source = covid();
+        let script = format!(
+            "select location from {source} where new_deaths > 100; \
+            select missing from {source}; \
+            select location, new_cases from {source} limit 2"
+        );
+        let results = run_script(&script, &QueryOptions::default()).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(Error::UnknownColumn { .. })));
+
+        let options = QueryOptions {
+            on_error: OnError::Continue,
+            ..Default::default()
+        };
+        let results = run_script(&script, &options).await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[2].as_ref().unwrap().shape(), (2, 2));
     }
 
-    let sql1 = &ast[0];
-    info!("sql ==================== {:#?}", sql1);
-    let Sql {
-        source,
-        condition,
-        selection,
-        offset,
-        limit,
-        order_by,
-    } = sql1.try_into()?;
-
-    info!("retrieving data from {source}");
-    info!("retrieving data from {:#?}", condition);
-
-    let ds = detect_content(retrieve_data(source).await?).load()?;
-
-    let mut filtered = match condition {
-        Some(expr) => ds.0.lazy().filter(expr),
-        None => ds.0.lazy(),
-    };
-
-    filtered = order_by.into_iter().fold(filtered, |acc, (col, desc)| {
-        acc.sort(
-            &[col],
-            SortMultipleOptions::new().with_order_descending(desc),
-        )
-    });
-
-    if offset.is_some() || limit.is_some() {
-        filtered = filtered.slice(offset.unwrap_or(0), limit.unwrap_or(usize::MAX) as u32);
+    #[tokio::test]
+    async fn it_rejects_scripts_in_query() {
+        let source = covid();
+        let err = query(format!("select a from {source}; select b from {source}"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::UnsupportedFeature { .. }));
     }
 
-    Ok(DataSet(filtered.select(selection).collect()?))
+    #[tokio::test]
+    async fn it_queries_in_memory_data() {
+        let df = df!("a" => [1i64, 2, 3]).unwrap();
+        let ds = query_dataframe("select a from t where a > 1", "t", df)
+            .await
+            .unwrap();
+        assert_eq!(ds.height(), 2);
+        let ds = query_content("select b from t where a = 2", "t", "a,b\n1,x\n2,y\n")
+            .await
+            .unwrap();
+        assert_eq!(ds.column("b").unwrap().str().unwrap().get(0), Some("y"));
+
+        let content = String::from("a\n4\n").into_bytes();
+        let ds = query_content("select a from t", "t", content.as_slice())
+            .await
+            .unwrap();
+        assert_eq!(ds.column("a").unwrap().i64().unwrap().get(0), Some(4));
+    }
+
+    #[tokio::test]
+    async fn it_reuses_files_parsed_by_earlier_calls() {
+        use crate::frame_cache::{fingerprint, FrameKey};
+
+        let dir = TempDir::new("shared");
+        let path = dir.join("data.csv");
+        std::fs::write(&path, "a\n1\n2\n").unwrap();
+        let source = format!("file://{}", path.display());
+        let sql = format!("select sum(a) total from '{source}'");
+        let total = |ds: DataSet| ds.column("total").unwrap().i64().unwrap().get(0);
+
+        assert_eq!(total(query(&sql).await.unwrap()), Some(3));
+        // stand in for the parsed file: only a cache hit returns it.
+        let key = FrameKey {
+            source: source.clone(),
+            options: ReadOptions::default(),
+            fingerprint: fingerprint(b"a\n1\n2\n"),
+        };
+        let cache = shared_frame_cache();
+        cache
+            .lock()
+            .unwrap()
+            .insert(key, df!("a" => [10i64]).unwrap());
+        assert_eq!(total(query(&sql).await.unwrap()), Some(10));
+        assert_eq!(cache.lock().unwrap().invalidate(&source), 1);
+    }
 }
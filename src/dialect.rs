@@ -20,49 +20,135 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 use sqlparser::{
-    dialect::{Dialect, GenericDialect},
-    parser::Parser,
+    ast::{MacroArg, MacroDefinition, Statement},
+    dialect::Dialect,
+    keywords::Keyword,
+    parser::{Parser, ParserError},
+    tokenizer::Token,
 };
+
 #[derive(Debug, Default)]
 pub struct OrinDialect;
 
-// support identifier can be a url
+// support identifier can be a url, or contain `${variable}` references
 impl Dialect for OrinDialect {
     fn is_identifier_start(&self, ch: char) -> bool {
-        ('a'..='z').contains(&ch) || ('A'..='Z').contains(&ch) || ch == '_' || ch == '.'
+        ch.is_ascii_alphabetic() || ch == '_' || ch == '.' || ch == '$'
     }
 
     fn is_identifier_part(&self, ch: char) -> bool {
-        ('a'..='z').contains(&ch)
-            || ('A'..='Z').contains(&ch)
-            || ('0'..='9').contains(&ch)
-            || [':', '<', '>', '/', '?', '&', '=', '_', '.', '-'].contains(&ch)
+        ch.is_ascii_alphanumeric()
+            || [
+                ':', '<', '>', '/', '?', '&', '=', '_', '.', '-', '$', '{', '}',
+            ]
+            .contains(&ch)
+    }
+
+    // sqlparser only parses `CREATE MACRO` for the duckdb and generic dialects
+    fn parse_statement(&self, parser: &mut Parser) -> Option<Result<Statement, ParserError>> {
+        let keyword = |n| match parser.peek_nth_token(n).token {
+            Token::Word(word) => word.keyword,
+            _ => Keyword::NoKeyword,
+        };
+        if keyword(0) != Keyword::CREATE {
+            return None;
+        }
+        let or_replace = keyword(1) == Keyword::OR && keyword(2) == Keyword::REPLACE;
+        let mut n = if or_replace { 3 } else { 1 };
+        let temporary = matches!(keyword(n), Keyword::TEMP | Keyword::TEMPORARY);
+        if temporary {
+            n += 1;
+        }
+        if keyword(n) != Keyword::MACRO {
+            return None;
+        }
+        for _ in 0..=n {
+            parser.next_token();
+        }
+        Some(parse_macro(parser, or_replace, temporary))
     }
 }
 
-fn example_sql() -> String {
-    let url = "https://raw.githubusercontent.com/owid/covid-19-data/master/public/data/latest/owid-covid-latest.csv";
-    // let url = "owid-covid-latest.csv";
-    let sql = format!(
-        "select location name, total_cases, new_cases, total_deaths, new_deaths \
-    from {} \
-    where new_deaths > 500 \
-    order by new_cases desc \
-    limit 6 offset 5",
-        url
-    );
-    sql
+/// `name([arg [:= default], ...]) AS expr | TABLE query`
+fn parse_macro(
+    parser: &mut Parser,
+    or_replace: bool,
+    temporary: bool,
+) -> Result<Statement, ParserError> {
+    let name = parser.parse_object_name(false)?;
+    parser.expect_token(&Token::LParen)?;
+    let args = match parser.consume_token(&Token::RParen) {
+        true => None,
+        false => {
+            let args = parser.parse_comma_separated(|p| {
+                let name = p.parse_identifier(false)?;
+                let default_expr =
+                    match p.consume_token(&Token::Assignment) || p.consume_token(&Token::RArrow) {
+                        true => Some(p.parse_expr()?),
+                        false => None,
+                    };
+                Ok(MacroArg { name, default_expr })
+            })?;
+            parser.expect_token(&Token::RParen)?;
+            Some(args)
+        }
+    };
+    parser.expect_keyword(Keyword::AS)?;
+    let definition = match parser.parse_keyword(Keyword::TABLE) {
+        true => MacroDefinition::Table(parser.parse_query()?),
+        false => MacroDefinition::Expr(parser.parse_expr()?),
+    };
+    Ok(Statement::CreateMacro {
+        or_replace,
+        temporary,
+        name,
+        args,
+        definition,
+    })
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use sqlparser::parser::Parser;
+
+    fn example_sql() -> String {
+        let url = "https://raw.githubusercontent.com/owid/covid-19-data/master/public/data/latest/owid-covid-latest.csv";
+        // let url = "owid-covid-latest.csv";
+        let sql = format!(
+            "select location name, total_cases, new_cases, total_deaths, new_deaths \
+        from {} \
+        where new_deaths > 500 \
+        order by new_cases desc \
+        limit 6 offset 5",
+            url
+        );
+        sql
+    }
+
+    #[test]
+    fn it_parses_macros_and_variables() {
+        let sql = "create or replace temp macro per_million(x, pop := 1) as x * 1e6 / pop; \
+            select ${column} from file:///data/${year}.csv";
+        let ast = Parser::parse_sql(&OrinDialect, sql).unwrap();
+        assert!(matches!(
+            &ast[0],
+            Statement::CreateMacro {
+                or_replace: true,
+                temporary: true,
+                args: Some(args),
+                ..
+            } if args.len() == 2 && args[1].default_expr.is_some()
+        ));
+        assert_eq!(
+            ast[1].to_string(),
+            "SELECT ${column} FROM file:///data/${year}.csv"
+        );
+    }
 
     #[test]
     fn it_works() {
         let sql = example_sql();
-        println!("{:#?}", Parser::parse_sql(&OrinDialect::default(), &sql));
-        // assert!(Parser::parse_sql(&OrinDialect::default(), &sql).is_ok());
+        println!("{:#?}", Parser::parse_sql(&OrinDialect, &sql));
+        // assert!(Parser::parse_sql(&OrinDialect, &sql).is_ok());
     }
 }
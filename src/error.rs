@@ -0,0 +1,415 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::fmt;
+
+use polars::error::PolarsError;
+use sqlparser::parser::ParserError;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Byte range of the offending fragment inside the original sql text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// the sql text could not be parsed, `position` is the 1-based line and
+    /// column reported by the parser.
+    ParseError {
+        message: String,
+        position: Option<(usize, usize)>,
+        span: Option<Span>,
+    },
+    /// the sql is valid but uses something we can not translate yet.
+    UnsupportedFeature {
+        feature: String,
+        snippet: String,
+        span: Option<Span>,
+    },
+    /// a referenced column does not exist in the data source.
+    UnknownColumn {
+        name: String,
+        suggestions: Vec<String>,
+        span: Option<Span>,
+    },
+    /// a bare table name is neither registered in the session nor a url.
+    UnknownTable {
+        name: String,
+        suggestions: Vec<String>,
+        span: Option<Span>,
+    },
+    /// a catalog statement conflicts with the tables of the session, e.g. a
+    /// `CREATE` of an existing name or a `DROP VIEW` of a table.
+    CatalogError {
+        message: String,
+        name: String,
+        span: Option<Span>,
+    },
+    /// an expression is applied to operands of the wrong type.
+    TypeMismatch {
+        message: String,
+        snippet: String,
+        span: Option<Span>,
+    },
+    /// the data source could not be retrieved.
+    FetchError { url: String, cause: BoxError },
+    /// the retrieved content could not be loaded into a DataFrame.
+    LoadError { cause: BoxError },
+    /// the result could not be written to its target.
+    WriteError { target: String, cause: BoxError },
+    /// polars failed while executing the translated plan.
+    ExecutionError(PolarsError),
+}
+
+impl Error {
+    pub fn unsupported(feature: impl Into<String>, snippet: impl ToString) -> Self {
+        Error::UnsupportedFeature {
+            feature: feature.into(),
+            snippet: snippet.to_string(),
+            span: None,
+        }
+    }
+
+    pub fn unknown_column(name: impl Into<String>, suggestions: Vec<String>) -> Self {
+        Error::UnknownColumn {
+            name: name.into(),
+            suggestions,
+            span: None,
+        }
+    }
+
+    pub fn unknown_table(name: impl Into<String>, suggestions: Vec<String>) -> Self {
+        Error::UnknownTable {
+            name: name.into(),
+            suggestions,
+            span: None,
+        }
+    }
+
+    pub fn catalog(message: impl Into<String>, name: impl Into<String>) -> Self {
+        Error::CatalogError {
+            message: message.into(),
+            name: name.into(),
+            span: None,
+        }
+    }
+
+    pub fn type_mismatch(message: impl Into<String>, snippet: impl ToString) -> Self {
+        Error::TypeMismatch {
+            message: message.into(),
+            snippet: snippet.to_string(),
+            span: None,
+        }
+    }
+
+    pub fn fetch(url: impl Into<String>, cause: impl Into<BoxError>) -> Self {
+        Error::FetchError {
+            url: url.into(),
+            cause: cause.into(),
+        }
+    }
+
+    pub fn write(target: impl Into<String>, cause: impl Into<BoxError>) -> Self {
+        Error::WriteError {
+            target: target.into(),
+            cause: cause.into(),
+        }
+    }
+
+    pub fn load(cause: impl Into<BoxError>) -> Self {
+        Error::LoadError {
+            cause: cause.into(),
+        }
+    }
+
+    /// Whether the error was caused by the query itself (HTTP 400), as opposed
+    /// to an upstream source or the execution engine (HTTP 502).
+    pub fn is_query_error(&self) -> bool {
+        matches!(
+            self,
+            Error::ParseError { .. }
+                | Error::UnsupportedFeature { .. }
+                | Error::UnknownColumn { .. }
+                | Error::UnknownTable { .. }
+                | Error::CatalogError { .. }
+                | Error::TypeMismatch { .. }
+        )
+    }
+
+    /// The sql fragment the error points at, if any.
+    pub fn snippet(&self) -> Option<&str> {
+        match self {
+            Error::UnsupportedFeature { snippet, .. } | Error::TypeMismatch { snippet, .. } => {
+                Some(snippet)
+            }
+            Error::UnknownColumn { name, .. }
+            | Error::UnknownTable { name, .. }
+            | Error::CatalogError { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::ParseError { span, .. }
+            | Error::UnsupportedFeature { span, .. }
+            | Error::UnknownColumn { span, .. }
+            | Error::UnknownTable { span, .. }
+            | Error::CatalogError { span, .. }
+            | Error::TypeMismatch { span, .. } => *span,
+            _ => None,
+        }
+    }
+
+    /// Resolve the position of the snippet inside `sql`, so that `render` can
+    /// point at it. Errors which already know their position are left as is.
+    pub fn locate(mut self, sql: &str) -> Self {
+        if self.span().is_some() {
+            return self;
+        }
+        let found = match &self {
+            Error::ParseError {
+                position: Some((line, column)),
+                ..
+            } => find_position(sql, *line, *column),
+            _ => self
+                .snippet()
+                .and_then(|snippet| find_fragment(sql, snippet)),
+        };
+        match &mut self {
+            Error::ParseError { span, .. }
+            | Error::UnsupportedFeature { span, .. }
+            | Error::UnknownColumn { span, .. }
+            | Error::UnknownTable { span, .. }
+            | Error::CatalogError { span, .. }
+            | Error::TypeMismatch { span, .. } => *span = found,
+            _ => {}
+        }
+        self
+    }
+
+    /// Render the error together with the offending line of `sql` and a caret
+    /// marker underneath the fragment, e.g.
+    ///
+    /// ```text
+    /// unknown column `new_death`, did you mean `new_deaths`?
+    ///   |
+    /// 1 | select new_death from data
+    ///   |        ^^^^^^^^^
+    /// ```
+    pub fn render(&self, sql: &str) -> String {
+        let span = match self.span() {
+            Some(span) if span.start <= sql.len() => span,
+            _ => return self.to_string(),
+        };
+        let line_start = sql[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = sql[span.start..]
+            .find('\n')
+            .map_or(sql.len(), |i| span.start + i);
+        let line_no = sql[..line_start].matches('\n').count() + 1;
+        let column = sql[line_start..span.start].chars().count();
+        let width = sql[span.start..span.end.clamp(span.start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let gutter = " ".repeat(line_no.to_string().len());
+        format!(
+            "{self}\n{gutter} |\n{line_no} | {}\n{gutter} | {}{}",
+            &sql[line_start..line_end],
+            " ".repeat(column),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ParseError { message, .. } => write!(f, "failed to parse sql: {message}"),
+            Error::UnsupportedFeature {
+                feature, snippet, ..
+            } => write!(f, "{feature} is not supported at the moment: `{snippet}`"),
+            Error::UnknownColumn {
+                name, suggestions, ..
+            } => {
+                write!(f, "unknown column `{name}`")?;
+                write_suggestions(f, suggestions)
+            }
+            Error::UnknownTable {
+                name, suggestions, ..
+            } => {
+                write!(f, "unknown table `{name}`")?;
+                write_suggestions(f, suggestions)
+            }
+            Error::CatalogError { message, name, .. } => write!(f, "`{name}` {message}"),
+            Error::TypeMismatch {
+                message, snippet, ..
+            } => write!(f, "type mismatch in `{snippet}`: {message}"),
+            Error::FetchError { url, cause } => write!(f, "failed to fetch {url}: {cause}"),
+            Error::LoadError { cause } => write!(f, "failed to load data: {cause}"),
+            Error::WriteError { target, cause } => write!(f, "failed to write {target}: {cause}"),
+            Error::ExecutionError(e) => write!(f, "failed to execute query: {e}"),
+        }
+    }
+}
+
+fn write_suggestions(f: &mut fmt::Formatter<'_>, suggestions: &[String]) -> fmt::Result {
+    match suggestions {
+        [] => Ok(()),
+        [one] => write!(f, ", did you mean `{one}`?"),
+        many => write!(f, ", did you mean one of `{}`?", many.join("`, `")),
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::FetchError { cause, .. }
+            | Error::LoadError { cause }
+            | Error::WriteError { cause, .. } => Some(cause.as_ref()),
+            Error::ExecutionError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParserError> for Error {
+    fn from(e: ParserError) -> Self {
+        let message = match e {
+            ParserError::TokenizerError(msg) | ParserError::ParserError(msg) => msg,
+            ParserError::RecursionLimitExceeded => "recursion limit exceeded".to_owned(),
+        };
+        // sqlparser appends the position as " at Line: {line}, Column {column}"
+        let (message, position) = match message.rfind(" at Line: ") {
+            Some(idx) => {
+                let position = parse_position(&message[idx + 10..]);
+                (message[..idx].to_owned(), position)
+            }
+            None => (message, None),
+        };
+        Error::ParseError {
+            message,
+            position,
+            span: None,
+        }
+    }
+}
+
+impl From<PolarsError> for Error {
+    fn from(e: PolarsError) -> Self {
+        Error::ExecutionError(e)
+    }
+}
+
+fn parse_position(s: &str) -> Option<(usize, usize)> {
+    let (line, column) = s.split_once(", Column ")?;
+    Some((line.trim().parse().ok()?, column.trim().parse().ok()?))
+}
+
+/// Find `fragment` inside `sql`, ignoring ascii case, and return its byte
+/// range. Matches inside a longer identifier, e.g. `ca` in `location`, are
+/// skipped.
+fn find_fragment(sql: &str, fragment: &str) -> Option<Span> {
+    if fragment.is_empty() {
+        return None;
+    }
+    let haystack = sql.to_ascii_lowercase();
+    let needle = fragment.to_ascii_lowercase();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let starts_word = needle.starts_with(is_word);
+    let ends_word = needle.ends_with(is_word);
+    haystack
+        .match_indices(&needle)
+        .map(|(start, _)| Span {
+            start,
+            end: start + needle.len(),
+        })
+        .find(|span| {
+            let before = haystack[..span.start].chars().next_back();
+            let after = haystack[span.end..].chars().next();
+            let joined_before = starts_word && before.is_some_and(is_word);
+            let joined_after = ends_word && after.is_some_and(is_word);
+            !joined_before && !joined_after
+        })
+}
+
+/// Turn a 1-based line / column into the byte range of the token starting there.
+fn find_position(sql: &str, line: usize, column: usize) -> Option<Span> {
+    let line_start = match line {
+        0 => return None,
+        1 => 0,
+        n => sql.match_indices('\n').nth(n - 2)?.0 + 1,
+    };
+    let start = line_start
+        + sql[line_start..]
+            .char_indices()
+            .nth(column.saturating_sub(1))
+            .map(|(i, _)| i)?;
+    let end = sql[start..]
+        .find(char::is_whitespace)
+        .map_or(sql.len(), |i| start + i);
+    Some(Span { start, end })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dialect::OrinDialect;
+    use sqlparser::parser::Parser;
+
+    #[test]
+    fn it_renders_a_caret_under_the_snippet() {
+        let sql = "select a\nfrom t where b like 'x'";
+        let err = Error::unsupported("expression", "b LIKE 'x'").locate(sql);
+        assert_eq!(err.span(), Some(Span { start: 22, end: 32 }));
+        assert_eq!(
+            err.render(sql),
+            "expression is not supported at the moment: `b LIKE 'x'`\n  \
+            |\n2 | from t where b like 'x'\n  |              ^^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn it_locates_whole_identifiers() {
+        let sql = "select location, ca from t";
+        let err = Error::unknown_column("ca", vec![]).locate(sql);
+        assert_eq!(err.span(), Some(Span { start: 17, end: 19 }));
+        assert_eq!(find_fragment("select cases from t", "ca"), None);
+    }
+
+    #[test]
+    fn it_locates_parse_errors() {
+        let sql = "select a\nfrom t where > 1";
+        let err: Error = Parser::parse_sql(&OrinDialect, sql).unwrap_err().into();
+        assert!(err.is_query_error());
+        let err = err.locate(sql);
+        assert_eq!(err.span(), Some(Span { start: 22, end: 23 }));
+        assert!(err
+            .render(sql)
+            .ends_with("2 | from t where > 1\n  |              ^"));
+    }
+}
@@ -0,0 +1,174 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use polars::prelude::*;
+
+use crate::error::Result;
+
+/// What a session records while planning a statement under `EXPLAIN`.
+#[derive(Default)]
+pub struct Profile {
+    /// the translated `Sql`, pretty printed.
+    pub(crate) translated: Option<String>,
+    /// fetch and load of every source retrieved while planning.
+    pub(crate) timings: Vec<Timing>,
+    /// whether the plan is run, counting the rows left after each stage.
+    pub(crate) analyze: bool,
+    /// each stage of the query: scan, filter, sort, slice, projection, with
+    /// the rows it yielded once the plan has run under `EXPLAIN ANALYZE`.
+    pub(crate) stages: Vec<(&'static str, Arc<Mutex<Option<usize>>>)>,
+}
+
+impl Profile {
+    /// Record a stage of the query. Under `EXPLAIN ANALYZE` the plan counts
+    /// the rows passing through it as it runs, so the query only runs once.
+    pub(crate) fn stage(&mut self, name: &'static str, plan: LazyFrame) -> LazyFrame {
+        let rows = Arc::new(Mutex::new(None));
+        self.stages.push((name, rows.clone()));
+        if !self.analyze {
+            return plan;
+        }
+        // pushing filters or slices through the count would change it.
+        let optimizations = AllowedOptimizations {
+            predicate_pushdown: false,
+            slice_pushdown: false,
+            ..Default::default()
+        };
+        let count = move |df: DataFrame| {
+            *rows.lock().unwrap() = Some(df.height());
+            Ok(df)
+        };
+        plan.map(count, optimizations, None, Some(name))
+    }
+}
+
+pub struct Timing {
+    pub(crate) step: &'static str,
+    pub(crate) source: String,
+    pub(crate) rows: Option<usize>,
+    pub(crate) elapsed: Duration,
+}
+
+impl std::fmt::Debug for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stages: Vec<_> = self.stages.iter().map(|(name, _)| name).collect();
+        f.debug_struct("Profile")
+            .field("translated", &self.translated)
+            .field("timings", &self.timings.len())
+            .field("stages", &stages)
+            .finish()
+    }
+}
+
+/// `EXPLAIN`: the normalised statement, the translated `Sql` and the logical
+/// plan before and after polars' optimisations.
+pub fn explain(statement: &str, profile: Profile, plan: &LazyFrame) -> Result<DataFrame> {
+    let steps = ["statement", "translated", "logical_plan", "optimized_plan"];
+    let details = [
+        statement.to_owned(),
+        profile.translated.unwrap_or_default(),
+        plan.describe_plan(),
+        plan.describe_optimized_plan()?,
+    ];
+    Ok(df!("step" => steps, "detail" => details)?)
+}
+
+/// `EXPLAIN ANALYZE`: run the plan and report the time spent fetching and
+/// loading every source, the rows left after each stage and the collect time.
+pub fn analyze(profile: Profile, plan: LazyFrame) -> Result<DataFrame> {
+    let (mut steps, mut rows, mut elapsed, mut details) = (vec![], vec![], vec![], vec![]);
+    for timing in profile.timings {
+        steps.push(timing.step);
+        rows.push(timing.rows.map(|r| r as u64));
+        elapsed.push(Some(millis(timing.elapsed)));
+        details.push(Some(timing.source));
+    }
+    let start = Instant::now();
+    let result = plan.collect()?;
+    let collected = start.elapsed();
+    for (stage, count) in profile.stages {
+        steps.push(stage);
+        rows.push(count.lock().unwrap().map(|r| r as u64));
+        elapsed.push(None);
+        details.push(None);
+    }
+    steps.push("collect");
+    rows.push(Some(result.height() as u64));
+    elapsed.push(Some(millis(collected)));
+    details.push(None);
+
+    Ok(df!(
+        "step" => steps,
+        "rows" => rows,
+        "elapsed_ms" => elapsed,
+        "detail" => details,
+    )?)
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::session::Session;
+
+    #[tokio::test]
+    async fn it_explains_queries() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut session = Session::default();
+        session.register_dataframe("numbers", df!("a" => [1, 2, 3, 4]).unwrap());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        session.register_udf("seen", vec![DataType::Int32], DataType::Int32, move |s| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            Ok(s[0].clone())
+        });
+        let sql = "select a from numbers where seen(a) > 1 order by a desc limit 2";
+
+        let explained = session.query(format!("explain {sql}")).await.unwrap();
+        let steps = explained.column("step").unwrap().str().unwrap();
+        assert_eq!(
+            steps.into_no_null_iter().collect::<Vec<_>>(),
+            ["statement", "translated", "logical_plan", "optimized_plan"]
+        );
+
+        let analyzed = session
+            .query(format!("explain analyze {sql}"))
+            .await
+            .unwrap();
+        let rows: Vec<_> = analyzed
+            .column("rows")
+            .unwrap()
+            .u64()
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(rows, [Some(4), Some(3), Some(3), Some(3), Some(2), Some(2)]);
+        // the stage counts are taken while the plan runs, once.
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -19,62 +19,649 @@
 // LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
-use anyhow::{anyhow, Error, Result};
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, OnceLock},
+    time::SystemTime,
+};
+
 use async_trait::async_trait;
-use tokio::fs;
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream, Stream, StreamExt, TryStreamExt};
+use percent_encoding::percent_decode_str;
+use reqwest::header::{
+    HeaderMap, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_TYPE, LAST_MODIFIED,
+};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt},
+    sync::Mutex,
+};
+use tokio_util::io::ReaderStream;
+use url::Url;
+
+use crate::{
+    archive::ArchiveFetcher,
+    error::{BoxError, Error, Result},
+    s3::S3Fetcher,
+};
 
+pub use crate::http::UrlFetcher;
+
+/// Retrieves the content of the urls of one (or more) schemes.
 #[async_trait]
-pub trait Fetcher {
+pub trait Fetcher: Send + Sync {
     type Error;
-    async fn fetch(&self) -> Result<String, Self::Error>;
+    async fn fetch(&self, url: &Url) -> Result<Content, Self::Error>;
+
+    /// The urls matched by a url holding a glob pattern, each fetched on its
+    /// own. By default the url itself.
+    async fn list(&self, url: &Url) -> Result<Vec<Url>, Self::Error> {
+        Ok(vec![url.clone()])
+    }
+}
+
+/// A fetcher as stored in a [`FetcherRegistry`], shared by its clones.
+pub type SharedFetcher = Arc<dyn Fetcher<Error = BoxError>>;
+
+/// The chunks of a fetched payload, in order.
+pub type ByteStream = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>;
+
+/// What is known about a payload before reading it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// the mime type, without parameters.
+    pub content_type: Option<String>,
+    /// the `Content-Encoding` the payload is compressed with.
+    pub content_encoding: Option<String>,
+    pub file_name: Option<String>,
+    pub last_modified: Option<SystemTime>,
+    /// the length in bytes, when known upfront.
+    pub content_length: Option<u64>,
+}
+
+/// A fetched payload, streamed as it is read.
+pub struct Content {
+    pub metadata: Metadata,
+    pub body: ByteStream,
+}
+
+impl Content {
+    pub fn new(metadata: Metadata, body: ByteStream) -> Self {
+        Content { metadata, body }
+    }
+
+    /// A payload already held in memory.
+    pub fn from_bytes(metadata: Metadata, bytes: impl Into<Bytes>) -> Self {
+        let bytes = bytes.into();
+        let metadata = Metadata {
+            content_length: Some(bytes.len() as u64),
+            ..metadata
+        };
+        Content::new(metadata, Box::pin(stream::once(async { Ok(bytes) })))
+    }
+
+    /// Read the whole payload into memory.
+    pub async fn bytes(mut self) -> io::Result<Bytes> {
+        let capacity = self.metadata.content_length.unwrap_or_default();
+        let mut buf = BytesMut::with_capacity(capacity.min(1 << 30) as usize);
+        while let Some(chunk) = self.body.next().await {
+            buf.extend_from_slice(&chunk?);
+        }
+        Ok(buf.freeze())
+    }
+}
+
+impl fmt::Debug for Content {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Content")
+            .field("metadata", &self.metadata)
+            .finish_non_exhaustive()
+    }
 }
 
-struct UrlFetcher<'a>(pub(crate) &'a str);
+/// The streamed body of an http `response` to a request for `url`, and the
+/// metadata read from its headers.
+pub(crate) fn response_content(response: reqwest::Response, url: &Url) -> Content {
+    let headers = response.headers();
+    let metadata = Metadata {
+        content_type: header(headers, CONTENT_TYPE)
+            .and_then(|t| t.split(';').next())
+            .map(|t| t.trim().to_lowercase()),
+        content_encoding: header(headers, CONTENT_ENCODING).map(str::to_owned),
+        file_name: header(headers, CONTENT_DISPOSITION)
+            .and_then(attachment_name)
+            .or_else(|| last_segment(url)),
+        last_modified: header(headers, LAST_MODIFIED)
+            .and_then(|date| httpdate::parse_http_date(date).ok()),
+        content_length: response.content_length(),
+    };
+    let body = response.bytes_stream().map_err(io::Error::other);
+    Content::new(metadata, Box::pin(body))
+}
+
+/// The url `FROM stdin` and `FROM '-'` read.
+pub const STDIN: &str = "stdin:";
+
+/// Reads the standard input, or another reader, once: later fetches get the
+/// content read by the first one, so a script can query it repeatedly.
+pub struct StdinFetcher {
+    input: Mutex<Input>,
+}
+
+enum Input {
+    /// the process' standard input when `None`.
+    Unread(Option<Box<dyn AsyncRead + Send + Unpin>>),
+    Read(Bytes),
+}
+
+impl Default for StdinFetcher {
+    fn default() -> Self {
+        StdinFetcher {
+            input: Mutex::new(Input::Unread(None)),
+        }
+    }
+}
+
+impl StdinFetcher {
+    /// Read `reader` in place of the standard input.
+    pub fn from_reader(reader: impl AsyncRead + Send + Unpin + 'static) -> Self {
+        StdinFetcher {
+            input: Mutex::new(Input::Unread(Some(Box::new(reader)))),
+        }
+    }
+}
 
 #[async_trait]
-impl<'a> Fetcher for UrlFetcher<'a> {
-    type Error = Error;
+impl Fetcher for StdinFetcher {
+    type Error = BoxError;
 
-    // http://
-    async fn fetch(&self) -> Result<String, Self::Error> {
-        Ok(reqwest::get(self.0).await?.text().await?)
+    // stdin:
+    async fn fetch(&self, _: &Url) -> Result<Content, Self::Error> {
+        let mut input = self.input.lock().await;
+        if let Input::Unread(reader) = &mut *input {
+            let mut buf = Vec::new();
+            match reader {
+                Some(reader) => reader.read_to_end(&mut buf).await?,
+                None => tokio::io::stdin().read_to_end(&mut buf).await?,
+            };
+            *input = Input::Read(buf.into());
+        }
+        match &*input {
+            Input::Read(bytes) => Ok(Content::from_bytes(Metadata::default(), bytes.clone())),
+            Input::Unread(_) => unreachable!("the input is read above"),
+        }
     }
 }
-struct FileFetcher<'a>(pub(crate) &'a str);
+
+/// Reads local files. A url holding a glob pattern, e.g.
+/// `file:///data/2024-*.csv`, or naming a directory lists the matching files.
+pub struct FileFetcher;
 
 #[async_trait]
-impl<'a> Fetcher for FileFetcher<'a> {
-    type Error = Error;
+impl Fetcher for FileFetcher {
+    type Error = BoxError;
 
     // file://
-    async fn fetch(&self) -> Result<String, Self::Error> {
-        let data = fs::read_to_string(&self.0[7..]).await?;
-        Ok(data)
-        // Ok(fs::read_to_string(&self.0[7..]).await?)
+    async fn fetch(&self, url: &Url) -> Result<Content, Self::Error> {
+        let path = url_path(url)?;
+        if path.is_dir() {
+            return Err(format!("{} is a directory", path.display()).into());
+        }
+        let file = File::open(&path).await.map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => format!("no such file: {}", path.display()),
+            _ => format!("{}: {e}", path.display()),
+        })?;
+        let stat = file.metadata().await?;
+        let metadata = Metadata {
+            content_type: None,
+            content_encoding: None,
+            file_name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            last_modified: stat.modified().ok(),
+            content_length: Some(stat.len()),
+        };
+        Ok(Content::new(metadata, Box::pin(ReaderStream::new(file))))
+    }
+
+    async fn list(&self, url: &Url) -> Result<Vec<Url>, Self::Error> {
+        let path = url_path(url)?;
+        let mut paths: Vec<PathBuf> = match path.to_str() {
+            Some(pattern) if pattern.contains(['*', '?', '[']) => glob::glob(pattern)?
+                .filter_map(|path| path.ok())
+                .filter(|path| path.is_file())
+                .collect(),
+            _ if path.is_dir() => {
+                let mut files = Vec::new();
+                walk(&path, &mut files)?;
+                files
+            }
+            _ => return Ok(vec![url.clone()]),
+        };
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|path| {
+                Url::from_file_path(&path)
+                    .map_err(|_| format!("not an absolute path: {}", path.display()).into())
+            })
+            .collect()
     }
 }
 
-pub async fn retrieve_data(source: impl AsRef<str>) -> Result<String, Error> {
-    println!("========= {:#?}",source.as_ref());
-    match &source.as_ref()[..4] {
-        "http" => UrlFetcher(source.as_ref()).fetch().await,
-        "file" => FileFetcher(source.as_ref()).fetch().await,
-        _ => Err(anyhow!(
-            "not support scheme, we only support http/https/file at the moment."
-        )),
+fn url_path(url: &Url) -> Result<PathBuf, BoxError> {
+    url.to_file_path()
+        .map_err(|_| format!("not a local file url: {url}").into())
+}
+
+/// Collect the files below `dir`, descending into hive style `key=value`
+/// partition directories and any other subdirectory.
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if is_hidden(&path) {
+            continue;
+        }
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else if path.is_file() {
+            files.push(path);
+        }
     }
+    Ok(())
 }
 
-// TODO: we will extract the scheme before retieve_data, retrieve_data's pattern shouldn't use such as
-//          "http" => UrlFetcher(source.as_ref()).fetch().await,
-//          "file" => FileFetcher(source.as_ref()).fetch().await,
-//
-// fn extract_scheme(source: impl AsRef<str>+ 'static) -> Result<Box<dyn Fetcher>> {
-//     match &source.as_ref()[..4] {
-//         "http" => Ok(Box::new(UrlFetcher(source.as_ref().to_owned()))),
-//         "file" => Ok(Box::new(FileFetcher(source.as_ref()))),
-//         _ => Err(anyhow!(
-//             "not support scheme, we only support http/https/file at the moment."
-//         )),
-//     }
-// }
+/// Dot files, and `_SUCCESS` style markers written next to partitioned data.
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with(['.', '_']))
+}
+
+fn header(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// The `filename` of a `Content-Disposition: attachment; filename="a.csv"`.
+fn attachment_name(disposition: &str) -> Option<String> {
+    disposition.split(';').find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        (key.trim().eq_ignore_ascii_case("filename"))
+            .then(|| value.trim().trim_matches('"').to_owned())
+    })
+}
+
+fn last_segment(url: &Url) -> Option<String> {
+    url.path_segments()?
+        .next_back()
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_owned)
+}
+
+/// The local path `source` names: a `file:` url as of RFC 8089, with an
+/// empty or `localhost` authority, a bare absolute or relative path, or a
+/// `~/` path. Relative paths, including the non standard `file://data.csv`,
+/// resolve against `base`, or the current directory. `None` for the urls of
+/// other schemes.
+pub fn local_path(source: &str, base: Option<&Path>) -> Option<PathBuf> {
+    let path = match source.get(..5) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("file:") => {
+            let rest = &source[5..];
+            let authority = rest.strip_prefix("//").unwrap_or(rest);
+            if rest.starts_with('/')
+                && (authority.starts_with('/') || authority.starts_with("localhost/"))
+            {
+                Url::parse(source).ok()?.to_file_path().ok()?
+            } else {
+                PathBuf::from(percent_decode_str(authority).decode_utf8().ok()?.as_ref())
+            }
+        }
+        _ if source.contains("://") || has_scheme(source) => return None,
+        _ => match source.strip_prefix('~') {
+            Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+                let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+                PathBuf::from(home).join(rest.trim_start_matches(['/', '\\']))
+            }
+            _ => PathBuf::from(source),
+        },
+    };
+    let path = match path.is_absolute() {
+        true => path,
+        false => match base {
+            Some(base) => base.join(path),
+            None => std::env::current_dir().ok()?.join(path),
+        },
+    };
+    Some(normalize(&path))
+}
+
+/// The extensions of files `FROM` reads, telling `cases.csv` is a file.
+const DATA_EXTENSIONS: [&str; 12] = [
+    "csv", "tsv", "txt", "json", "jsonl", "ndjson", "parquet", "gz", "zst", "bz2", "xz", "tar",
+];
+
+/// Whether `source` is meant as a local path rather than a table name: a
+/// `file:` url, a path with a separator or starting with `.` or `~`, a file
+/// name with a data file extension, or an existing file.
+pub fn is_local(source: &str, base: Option<&Path>) -> bool {
+    let file_url = source
+        .get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("file:"));
+    let data_file = Path::new(source)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| DATA_EXTENSIONS.contains(&extension.to_lowercase().as_str()));
+    file_url
+        || (!source.contains("://")
+            && !has_scheme(source)
+            && (source.starts_with(['.', '~'])
+                || source.contains(['/', '\\'])
+                || data_file
+                || local_path(source, base).is_some_and(|path| path.is_file())))
+}
+
+/// `source` as a `file:///` url when it names a local path, as is otherwise.
+pub fn source_url(source: &str, base: Option<&Path>) -> String {
+    local_path(source, base)
+        .and_then(|path| Url::from_file_path(path).ok())
+        .map_or_else(|| source.to_owned(), String::from)
+}
+
+/// Whether `source` starts with a url scheme, other than a windows drive
+/// letter.
+fn has_scheme(source: &str) -> bool {
+    Url::parse(source).is_ok_and(|url| url.scheme().len() > 1)
+}
+
+/// Resolve the `.` and `..` components of an absolute path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The fetchers of a session by (lowercase) url scheme. `file`, `http`,
+/// `https` and `s3` are registered by default, along with their `zip+` and
+/// `tar+` archive variants, and `stdin`.
+///
+/// The default fetchers are built once per process and shared by every
+/// default registry, so all sessions reuse the same http client and its
+/// connections. Registering a fetcher only changes the registry at hand.
+#[derive(Clone)]
+pub struct FetcherRegistry {
+    fetchers: HashMap<String, SharedFetcher>,
+}
+
+impl Default for FetcherRegistry {
+    fn default() -> Self {
+        static DEFAULT: OnceLock<FetcherRegistry> = OnceLock::new();
+        DEFAULT.get_or_init(FetcherRegistry::builtin).clone()
+    }
+}
+
+impl FetcherRegistry {
+    /// A registry with freshly built default fetchers.
+    fn builtin() -> Self {
+        let mut registry = FetcherRegistry::empty();
+        registry.register("file", FileFetcher);
+        registry.register("zip+file", ArchiveFetcher::zip(FileFetcher));
+        registry.register("tar+file", ArchiveFetcher::tar(FileFetcher));
+        let http = UrlFetcher::default();
+        registry.register_http(http.clone());
+        let s3 = S3Fetcher::from_env().with_http(http);
+        registry.register("zip+s3", ArchiveFetcher::zip(s3.clone()));
+        registry.register("tar+s3", ArchiveFetcher::tar(s3.clone()));
+        registry.register("s3", s3);
+        registry.register("stdin", StdinFetcher::default());
+        registry
+    }
+
+    /// A registry without any fetcher.
+    pub fn empty() -> Self {
+        FetcherRegistry {
+            fetchers: HashMap::new(),
+        }
+    }
+
+    /// Fetch urls of `scheme` with `fetcher`, replacing the previous one.
+    pub fn register<F>(&mut self, scheme: &str, fetcher: F)
+    where
+        F: Fetcher<Error = BoxError> + 'static,
+    {
+        self.fetchers
+            .insert(scheme.to_lowercase(), Arc::new(fetcher));
+    }
+
+    /// Fetch `http`, `https` and their archive variants with `fetcher`, e.g.
+    /// one built with custom [`HttpOptions`](crate::http::HttpOptions).
+    pub fn register_http(&mut self, fetcher: UrlFetcher) {
+        for scheme in ["http", "https"] {
+            self.register(
+                &format!("zip+{scheme}"),
+                ArchiveFetcher::zip(fetcher.clone()),
+            );
+            self.register(
+                &format!("tar+{scheme}"),
+                ArchiveFetcher::tar(fetcher.clone()),
+            );
+            self.register(scheme, fetcher.clone());
+        }
+    }
+
+    pub fn deregister(&mut self, scheme: &str) -> bool {
+        self.fetchers.remove(&scheme.to_lowercase()).is_some()
+    }
+
+    /// The registered schemes, sorted.
+    pub fn schemes(&self) -> Vec<&str> {
+        let mut schemes: Vec<_> = self.fetchers.keys().map(String::as_str).collect();
+        schemes.sort_unstable();
+        schemes
+    }
+
+    /// Fetch `source` with the fetcher registered for its scheme.
+    pub async fn fetch(&self, source: &str) -> Result<Content> {
+        let (url, fetcher) = self.fetcher(source)?;
+        fetcher
+            .fetch(&url)
+            .await
+            .map_err(|e| Error::fetch(source, e))
+    }
+
+    /// The urls matched by the glob pattern in `source`.
+    pub async fn list(&self, source: &str) -> Result<Vec<String>> {
+        let (url, fetcher) = self.fetcher(source)?;
+        let urls = fetcher
+            .list(&url)
+            .await
+            .map_err(|e| Error::fetch(source, e))?;
+        Ok(urls.into_iter().map(String::from).collect())
+    }
+
+    fn fetcher(&self, source: &str) -> Result<(Url, &SharedFetcher)> {
+        let url = Url::parse(source).map_err(|e| Error::fetch(source, e))?;
+        let fetcher = self.fetchers.get(url.scheme()).ok_or_else(|| {
+            Error::fetch(
+                source,
+                format!(
+                    "unsupported scheme `{}`, registered schemes are: {}",
+                    url.scheme(),
+                    self.schemes().join(", ")
+                ),
+            )
+        })?;
+        Ok((url, fetcher))
+    }
+}
+
+impl fmt::Debug for FetcherRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FetcherRegistry")
+            .field("schemes", &self.schemes())
+            .finish()
+    }
+}
+
+/// Fetch the whole content of `source` with the default fetchers.
+pub async fn retrieve_data(source: impl AsRef<str>) -> Result<(Metadata, Bytes)> {
+    let source = source.as_ref();
+    let content = FetcherRegistry::default().fetch(source).await?;
+    let metadata = content.metadata.clone();
+    let bytes = content.bytes().await.map_err(|e| Error::fetch(source, e))?;
+    Ok((metadata, bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    struct Constant;
+
+    #[async_trait]
+    impl Fetcher for Constant {
+        type Error = BoxError;
+
+        async fn fetch(&self, url: &Url) -> Result<Content, Self::Error> {
+            let body = format!("path\n{}\n", url.path());
+            Ok(Content::from_bytes(Metadata::default(), body))
+        }
+    }
+
+    #[tokio::test]
+    async fn it_dispatches_on_the_scheme() {
+        let mut registry = FetcherRegistry::default();
+        registry.register("MEM", Constant);
+        let content = registry.fetch("mem:/numbers").await.unwrap();
+        assert_eq!(content.metadata.content_length, Some(14));
+        assert_eq!(content.bytes().await.unwrap(), "path\n/numbers\n");
+
+        let err = registry.fetch("gs://bucket/key.csv").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to fetch gs://bucket/key.csv: unsupported scheme `gs`, registered schemes \
+            are: file, http, https, mem, s3, stdin, tar+file, tar+http, tar+https, tar+s3, \
+            zip+file, zip+http, zip+https, zip+s3"
+        );
+        assert!(registry.fetch("abc").await.is_err());
+    }
+
+    #[test]
+    fn it_shares_the_default_fetchers() {
+        let mut registry = FetcherRegistry::default();
+        let other = FetcherRegistry::default();
+        assert!(Arc::ptr_eq(
+            &registry.fetchers["https"],
+            &other.fetchers["https"]
+        ));
+        registry.register("mem", Constant);
+        assert!(registry.deregister("file"));
+        assert_eq!(FetcherRegistry::default().schemes(), other.schemes());
+        assert!(!other.schemes().contains(&"mem"));
+    }
+
+    #[tokio::test]
+    async fn it_streams_local_files() {
+        let source = format!(
+            "file://{}/owid-covid-latest.csv",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let (metadata, bytes) = retrieve_data(&source).await.unwrap();
+        assert_eq!(metadata.file_name.as_deref(), Some("owid-covid-latest.csv"));
+        assert_eq!(metadata.content_length, Some(bytes.len() as u64));
+        assert!(metadata.last_modified.is_some());
+        assert!(bytes.starts_with(b"iso_code,"));
+    }
+
+    #[tokio::test]
+    async fn it_lists_globs_and_directories() {
+        let dir = TempDir::new("glob");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        for name in [
+            "2024-02.csv",
+            "2024-01.csv",
+            "2023-12.csv",
+            ".hidden",
+            "nested/2022-01.csv",
+            "nested/_SUCCESS",
+        ] {
+            std::fs::write(dir.join(name), "a\n1\n").unwrap();
+        }
+        let names = |urls: Vec<Url>| -> Vec<String> {
+            urls.iter()
+                .map(|url| url.path_segments().unwrap().next_back().unwrap().to_owned())
+                .collect()
+        };
+
+        let pattern = Url::from_file_path(dir.join("2024-*.csv")).unwrap();
+        let listed = FileFetcher.list(&pattern).await.unwrap();
+        assert_eq!(names(listed), ["2024-01.csv", "2024-02.csv"]);
+        let directory = Url::from_directory_path(&dir).unwrap();
+        let listed = FileFetcher.list(&directory).await.unwrap();
+        assert_eq!(
+            names(listed),
+            ["2023-12.csv", "2024-01.csv", "2024-02.csv", "2022-01.csv"]
+        );
+        assert!(FileFetcher.fetch(&directory).await.is_err());
+    }
+
+    #[test]
+    fn it_resolves_local_paths() {
+        let base = Path::new("/base/sub");
+        let path = |source: &str| local_path(source, Some(base)).map(PathBuf::into_os_string);
+        assert_eq!(path("file:///data/a%20b.csv").unwrap(), "/data/a b.csv");
+        assert_eq!(path("file://localhost/data/a.csv").unwrap(), "/data/a.csv");
+        assert_eq!(path("file:/data/a.csv").unwrap(), "/data/a.csv");
+        assert_eq!(path("file://data/a.csv").unwrap(), "/base/sub/data/a.csv");
+        assert_eq!(path("../a.csv").unwrap(), "/base/a.csv");
+        assert_eq!(path("./data/*.csv").unwrap(), "/base/sub/data/*.csv");
+        if let Some(home) = std::env::var_os("HOME") {
+            assert_eq!(path("~/a.csv").unwrap(), Path::new(&home).join("a.csv"));
+        }
+        assert_eq!(path("https://example.com/a.csv"), None);
+
+        assert_eq!(
+            source_url("data dir/a.csv", Some(base)),
+            "file:///base/sub/data%20dir/a.csv"
+        );
+        assert_eq!(
+            source_url("s3://bucket/a.csv", Some(base)),
+            "s3://bucket/a.csv"
+        );
+        assert_eq!(source_url(STDIN, Some(base)), STDIN);
+        assert!(is_local("data/a.csv", Some(base)));
+        assert!(is_local("a.CSV", Some(base)));
+        assert!(!is_local("covid", Some(base)));
+    }
+
+    #[tokio::test]
+    async fn it_reports_missing_files() {
+        let url = source_url("missing.csv", Some(Path::new("/nowhere")));
+        let err = FetcherRegistry::default().fetch(&url).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to fetch file:///nowhere/missing.csv: no such file: /nowhere/missing.csv"
+        );
+    }
+
+    #[test]
+    fn it_reads_attachment_names() {
+        assert_eq!(
+            attachment_name("attachment; filename=\"cases.csv\"").as_deref(),
+            Some("cases.csv")
+        );
+        assert_eq!(attachment_name("inline"), None);
+    }
+}
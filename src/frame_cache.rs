@@ -0,0 +1,243 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hasher},
+};
+
+use polars::prelude::{DataFrame, Schema};
+
+use crate::loader::ReadOptions;
+
+/// The memory budget of a default [`FrameCache`], 256MiB.
+const DEFAULT_BUDGET: usize = 256 << 20;
+
+/// What a loaded DataFrame is cached under: the same source read with the
+/// same options is only parsed again once its content changes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FrameKey {
+    pub source: String,
+    pub options: ReadOptions,
+    /// the [`fingerprint`] of the fetched bytes.
+    pub fingerprint: u64,
+}
+
+/// A hash of fetched content, telling whether it changed since it was loaded.
+pub fn fingerprint(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+struct Entry {
+    df: DataFrame,
+    size: usize,
+    /// when the entry was last read, evicting the least recent first.
+    used: u64,
+}
+
+/// Parsed DataFrames, evicting the least recently used ones once their
+/// estimated size exceeds a memory budget.
+pub struct FrameCache {
+    entries: HashMap<FrameKey, Entry>,
+    budget: usize,
+    size: usize,
+    clock: u64,
+}
+
+impl Default for FrameCache {
+    fn default() -> Self {
+        FrameCache::new(DEFAULT_BUDGET)
+    }
+}
+
+impl FrameCache {
+    /// A cache holding up to `budget` bytes of DataFrames, none when `0`.
+    pub fn new(budget: usize) -> Self {
+        FrameCache {
+            entries: HashMap::new(),
+            budget,
+            size: 0,
+            clock: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: &FrameKey) -> Option<DataFrame> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.used = self.clock;
+        Some(entry.df.clone())
+    }
+
+    /// The columns of `source` read with `options`, as last loaded, without
+    /// telling whether its content changed since.
+    pub fn schema(&self, source: &str, options: &ReadOptions) -> Option<Schema> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key.source == source && key.options == *options)
+            .map(|(_, entry)| entry.df.schema())
+    }
+
+    /// Cache `df`, unless it alone exceeds the budget. Older content of the
+    /// same source and options is replaced.
+    pub fn insert(&mut self, key: FrameKey, df: DataFrame) {
+        self.entries.retain(|cached, entry| {
+            let stale = cached.source == key.source && cached.options == key.options;
+            if stale {
+                self.size -= entry.size;
+            }
+            !stale
+        });
+        let size = df.estimated_size();
+        if size > self.budget {
+            return;
+        }
+        while self.size + size > self.budget {
+            self.evict();
+        }
+        self.clock += 1;
+        self.size += size;
+        let used = self.clock;
+        self.entries.insert(key, Entry { df, size, used });
+    }
+
+    /// Drop the DataFrames loaded from `source`, returning how many there were.
+    pub fn invalidate(&mut self, source: &str) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|key, entry| {
+            let invalidated = key.source == source;
+            if invalidated {
+                self.size -= entry.size;
+            }
+            !invalidated
+        });
+        before - self.entries.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.size = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The estimated size in bytes of the cached DataFrames.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Change the memory budget, evicting entries until they fit in it.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        while self.size > self.budget {
+            self.evict();
+        }
+    }
+
+    fn evict(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.used)
+            .map(|(key, _)| key.clone());
+        if let Some(entry) = oldest.and_then(|key| self.entries.remove(&key)) {
+            self.size -= entry.size;
+        }
+    }
+}
+
+impl std::fmt::Debug for FrameCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameCache")
+            .field("entries", &self.entries.len())
+            .field("size", &self.size)
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use polars::df;
+
+    use super::*;
+    use crate::{data_set::DataSet, session::Session, test_util::TempDir};
+
+    fn key(source: &str, content: &[u8]) -> FrameKey {
+        FrameKey {
+            source: source.to_owned(),
+            options: ReadOptions::default(),
+            fingerprint: fingerprint(content),
+        }
+    }
+
+    #[test]
+    fn it_evicts_the_least_recently_used() {
+        let df = df!("a" => [1i64, 2, 3]).unwrap();
+        let size = df.estimated_size();
+        let mut cache = FrameCache::new(2 * size);
+        cache.insert(key("a.csv", b"a"), df.clone());
+        cache.insert(key("b.csv", b"b"), df.clone());
+        assert!(cache.get(&key("a.csv", b"a")).is_some());
+        cache.insert(key("c.csv", b"c"), df.clone());
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key("b.csv", b"b")).is_none());
+        assert_eq!(cache.size(), 2 * size);
+
+        cache.insert(key("a.csv", b"changed"), df.clone());
+        assert!(cache.get(&key("a.csv", b"a")).is_none());
+        assert_eq!(cache.invalidate("a.csv"), 1);
+        cache.set_budget(0);
+        assert!(cache.is_empty());
+        cache.insert(key("a.csv", b"a"), df);
+        assert_eq!(cache.size(), 0);
+    }
+
+    #[tokio::test]
+    async fn it_reuses_parsed_files() {
+        let dir = TempDir::new("frames");
+        let path = dir.join("data.csv");
+        std::fs::write(&path, "a\n1\n2\n").unwrap();
+        let sql = format!("select sum(a) total from 'file://{}'", path.display());
+        let total = |ds: DataSet| ds.column("total").unwrap().i64().unwrap().get(0);
+
+        let mut session = Session::default();
+        assert_eq!(total(session.query(&sql).await.unwrap()), Some(3));
+        assert_eq!(total(session.query(&sql).await.unwrap()), Some(3));
+        assert_eq!(session.frame_cache().len(), 1);
+        std::fs::write(&path, "a\n5\n").unwrap();
+        assert_eq!(total(session.query(&sql).await.unwrap()), Some(5));
+        assert_eq!(session.frame_cache().len(), 1);
+        let source = format!("file://{}", path.display());
+        assert_eq!(session.frame_cache().invalidate(&source), 1);
+    }
+}
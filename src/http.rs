@@ -0,0 +1,512 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::{
+    env, fmt,
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use reqwest::{
+    header::{HeaderName, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, RETRY_AFTER},
+    Client, RequestBuilder, Response, StatusCode,
+};
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::{
+    error::BoxError,
+    fetcher::{response_content, Content, Fetcher},
+    http_cache::{CacheEntry, HttpCache},
+};
+
+/// How much of an error page is kept in an [`HttpError`].
+const BODY_EXCERPT: usize = 256;
+
+/// Credentials sent with the http requests of the hosts they are meant for,
+/// see [`HttpOptions::auth_hosts`].
+#[derive(Clone, PartialEq, Eq)]
+pub enum Auth {
+    Bearer(String),
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+}
+
+impl Auth {
+    /// A bearer token from `SQLTOOLS_HTTP_TOKEN`, or basic credentials from
+    /// `SQLTOOLS_HTTP_USERNAME` and `SQLTOOLS_HTTP_PASSWORD`.
+    pub fn from_env() -> Option<Self> {
+        if let Ok(token) = env::var("SQLTOOLS_HTTP_TOKEN") {
+            return Some(Auth::Bearer(token));
+        }
+        Some(Auth::Basic {
+            username: env::var("SQLTOOLS_HTTP_USERNAME").ok()?,
+            password: env::var("SQLTOOLS_HTTP_PASSWORD").ok(),
+        })
+    }
+}
+
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Auth::Bearer(_) => f.write_str("Bearer(..)"),
+            Auth::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// How http(s) sources are requested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpOptions {
+    /// sent with every request, e.g. `Accept` or an api key.
+    pub headers: Vec<(String, String)>,
+    pub auth: Option<Auth>,
+    /// the hosts `auth` is sent to, no other host ever gets it. By default
+    /// the comma separated hosts of `SQLTOOLS_HTTP_AUTH_HOSTS`.
+    pub auth_hosts: Vec<String>,
+    pub connect_timeout: Option<Duration>,
+    /// the longest wait for the next chunk of a response.
+    pub read_timeout: Option<Duration>,
+    /// how many times a request failing with a 5xx or 429 status, or a
+    /// connection error, is retried.
+    pub retries: u32,
+    /// the wait before the first retry, doubled for each one after it.
+    pub backoff: Duration,
+    /// the longest wait between retries, including `Retry-After` delays.
+    pub max_backoff: Duration,
+    /// keep responses on disk, revalidating them instead of downloading
+    /// them again. Off by default.
+    pub cache: Option<HttpCache>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        HttpOptions {
+            headers: Vec::new(),
+            auth: Auth::from_env(),
+            auth_hosts: env::var("SQLTOOLS_HTTP_AUTH_HOSTS")
+                .map(|hosts| {
+                    hosts
+                        .split(',')
+                        .map(|host| host.trim().to_lowercase())
+                        .filter(|host| !host.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            connect_timeout: Some(Duration::from_secs(10)),
+            read_timeout: Some(Duration::from_secs(30)),
+            retries: 3,
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            cache: None,
+        }
+    }
+}
+
+/// A response with a non success status.
+#[derive(Debug)]
+pub struct HttpError {
+    pub status: StatusCode,
+    pub url: Url,
+    /// the start of the response body, usually an error page.
+    pub body: String,
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} from {}", self.status, self.url)?;
+        match self.body.trim() {
+            "" => Ok(()),
+            body => write!(f, ": {body}"),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+/// Reads http(s) urls through one shared client, so connections are reused
+/// across fetches and clones of the fetcher.
+#[derive(Debug, Clone)]
+pub struct UrlFetcher {
+    options: HttpOptions,
+    client: Client,
+}
+
+impl Default for UrlFetcher {
+    fn default() -> Self {
+        UrlFetcher::new(HttpOptions::default()).expect("the tls backend initializes")
+    }
+}
+
+impl UrlFetcher {
+    pub fn new(options: HttpOptions) -> Result<Self, BoxError> {
+        let mut builder = Client::builder();
+        if let Some(timeout) = options.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = options.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in &options.headers {
+            headers.append(
+                HeaderName::try_from(name.as_str())?,
+                HeaderValue::try_from(value.as_str())?,
+            );
+        }
+        let client = builder.default_headers(headers).build()?;
+        Ok(UrlFetcher { options, client })
+    }
+
+    pub fn options(&self) -> &HttpOptions {
+        &self.options
+    }
+
+    pub(crate) fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The credentials to send to the host of `url`, if any.
+    fn auth(&self, url: &Url) -> Option<&Auth> {
+        let host = url.host_str()?;
+        let allowed = self
+            .options
+            .auth_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host));
+        self.options.auth.as_ref().filter(|_| allowed)
+    }
+
+    fn request(&self, url: &Url, cached: Option<&CacheEntry>) -> RequestBuilder {
+        let mut request = self.client.get(url.clone());
+        if let Some(etag) = cached.and_then(|entry| entry.etag.as_deref()) {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(date) = cached.and_then(|entry| entry.metadata.last_modified) {
+            request = request.header(IF_MODIFIED_SINCE, httpdate::fmt_http_date(date));
+        }
+        match self.auth(url) {
+            Some(Auth::Bearer(token)) => request.bearer_auth(token),
+            Some(Auth::Basic { username, password }) => {
+                request.basic_auth(username, password.as_ref())
+            }
+            None => request,
+        }
+    }
+
+    /// Send the request of `url`, conditional on the validators of `cached`.
+    async fn send(&self, url: &Url, cached: Option<&CacheEntry>) -> Result<Response, BoxError> {
+        self.send_with_retries(url, || self.request(url, cached), cached.is_some())
+            .await
+    }
+
+    /// Send the request built by `request` for each attempt, retrying
+    /// transient failures with an exponential backoff, or after the delay of
+    /// a `Retry-After` header. A 304 is only a success when `conditional`.
+    pub(crate) async fn send_with_retries(
+        &self,
+        url: &Url,
+        request: impl Fn() -> RequestBuilder,
+        conditional: bool,
+    ) -> Result<Response, BoxError> {
+        let mut attempt = 0;
+        loop {
+            let (error, retry_after): (BoxError, _) = match request().send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) if response.status() == StatusCode::NOT_MODIFIED && conditional => {
+                    return Ok(response)
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = response
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(retry_after);
+                    let mut body = response.text().await.unwrap_or_default();
+                    if let Some((end, _)) = body.char_indices().nth(BODY_EXCERPT) {
+                        body.truncate(end);
+                    }
+                    let error = HttpError {
+                        status,
+                        url: url.clone(),
+                        body,
+                    };
+                    if !retryable(status) {
+                        return Err(error.into());
+                    }
+                    (error.into(), retry_after)
+                }
+                Err(e) if e.is_connect() || e.is_timeout() => (e.into(), None),
+                Err(e) => return Err(e.into()),
+            };
+            if attempt >= self.options.retries {
+                return Err(error);
+            }
+            let backoff = self.options.backoff.saturating_mul(1 << attempt.min(16));
+            let wait = retry_after.unwrap_or(backoff).min(self.options.max_backoff);
+            warn!("retrying {url} in {wait:?} after: {error}");
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[async_trait]
+impl Fetcher for UrlFetcher {
+    type Error = BoxError;
+
+    // http://, https://
+    async fn fetch(&self, url: &Url) -> Result<Content, Self::Error> {
+        let Some(cache) = &self.options.cache else {
+            let response = self.send(url, None).await?;
+            return Ok(response_content(response, url));
+        };
+        let cached = cache.get(url).unwrap_or_else(|e| {
+            warn!("ignoring the cache entry of {url}: {e}");
+            None
+        });
+        match &cached {
+            Some(entry) if cache.offline || cache.is_fresh(entry) => {
+                return Ok(cache.content(entry)?);
+            }
+            None if cache.offline => {
+                return Err(format!("{url} is not cached and the cache is offline").into());
+            }
+            _ => {}
+        }
+
+        let response = self.send(url, cached.as_ref()).await?;
+        if let Some(entry) = cached.filter(|_| response.status() == StatusCode::NOT_MODIFIED) {
+            debug!("{url} is not modified, reading it from the cache");
+            if let Err(e) = cache.touch(&entry) {
+                warn!("failed to refresh the cache entry of {url}: {e}");
+            }
+            return Ok(cache.content(&entry)?);
+        }
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_owned);
+        let content = response_content(response, url);
+        let metadata = content.metadata.clone();
+        let bytes = content.bytes().await?;
+        if let Err(e) = cache.put(url, etag, &metadata, &bytes) {
+            warn!("failed to cache {url}: {e}");
+        }
+        Ok(Content::from_bytes(metadata, bytes))
+    }
+}
+
+fn retryable(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// The delay of a `Retry-After` header, in seconds or as an http date.
+fn retry_after(value: &str) -> Option<Duration> {
+    match value.trim().parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .ok(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::test_util::TempDir;
+
+    /// A server answering `/flaky` with a 503 the first time, `/busy` always
+    /// with a 429, `/cached` with a 304 when its etag matches, and anything
+    /// else with a 404, recording the requests.
+    async fn serve() -> (Url, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let requests = Arc::new(Mutex::new(Vec::<String>::new()));
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8(request).unwrap().to_lowercase();
+                let path = request.split(' ').nth(1).unwrap().to_owned();
+                let (status, headers, body) = {
+                    let mut requests = received.lock().unwrap();
+                    let seen = requests.iter().filter(|r| r.contains(&path)).count();
+                    let revalidated = request.contains("if-none-match: \"v1\"\r\n");
+                    requests.push(request);
+                    match (path.as_str(), seen) {
+                        ("/cached", _) if revalidated => {
+                            ("304 Not Modified", "etag: \"v1\"\r\n", "")
+                        }
+                        ("/cached", _) => ("200 OK", "etag: \"v1\"\r\n", "a\n1\n"),
+                        ("/flaky", 0) => ("503 Service Unavailable", "retry-after: 0\r\n", ""),
+                        ("/flaky", _) => ("200 OK", "", "a\n1\n"),
+                        ("/busy", _) => ("429 Too Many Requests", "", ""),
+                        _ => ("404 Not Found", "", "<html>no such file</html>"),
+                    }
+                };
+                let response = format!(
+                    "HTTP/1.1 {status}\r\n{headers}content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (base, requests)
+    }
+
+    fn fetcher() -> UrlFetcher {
+        UrlFetcher::new(HttpOptions {
+            headers: vec![("X-Api-Key".to_owned(), "secret".to_owned())],
+            auth: Some(Auth::Bearer("token".to_owned())),
+            auth_hosts: vec!["127.0.0.1".to_owned()],
+            retries: 2,
+            backoff: Duration::from_millis(1),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_retries_transient_failures() {
+        let (base, requests) = serve().await;
+        let content = fetcher().fetch(&base.join("flaky").unwrap()).await.unwrap();
+        assert_eq!(content.bytes().await.unwrap(), "a\n1\n");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].contains("authorization: bearer token\r\n"));
+        assert!(requests[1].contains("x-api-key: secret\r\n"));
+    }
+
+    #[tokio::test]
+    async fn it_only_sends_auth_to_its_hosts() {
+        let (base, requests) = serve().await;
+        let fetcher = UrlFetcher::new(HttpOptions {
+            auth: Some(Auth::Bearer("token".to_owned())),
+            auth_hosts: vec!["example.com".to_owned()],
+            ..Default::default()
+        })
+        .unwrap();
+        fetcher.fetch(&base.join("cached").unwrap()).await.unwrap();
+        assert!(!requests.lock().unwrap()[0].contains("authorization"));
+    }
+
+    #[tokio::test]
+    async fn it_fails_on_error_statuses() {
+        let (base, requests) = serve().await;
+        let err = fetcher()
+            .fetch(&base.join("missing.csv").unwrap())
+            .await
+            .unwrap_err();
+        let err = err.downcast::<HttpError>().unwrap();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+        assert!(err
+            .to_string()
+            .ends_with("missing.csv: <html>no such file</html>"));
+
+        let err = fetcher()
+            .fetch(&base.join("busy").unwrap())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<HttpError>().unwrap().status,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        // one request for the missing file, three for the busy one.
+        assert_eq!(requests.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn it_revalidates_cached_responses() {
+        let (base, requests) = serve().await;
+        let dir = TempDir::new("http");
+        let cached = |cache: HttpCache| {
+            UrlFetcher::new(HttpOptions {
+                cache: Some(cache),
+                ..Default::default()
+            })
+            .unwrap()
+        };
+        let url = base.join("cached").unwrap();
+        let fetch = |fetcher: UrlFetcher| {
+            let url = url.clone();
+            async move {
+                fetcher
+                    .fetch(&url)
+                    .await?
+                    .bytes()
+                    .await
+                    .map_err(BoxError::from)
+            }
+        };
+
+        let revalidating = cached(HttpCache::new(&*dir));
+        assert_eq!(fetch(revalidating.clone()).await.unwrap(), "a\n1\n");
+        assert_eq!(fetch(revalidating).await.unwrap(), "a\n1\n");
+        assert!(requests.lock().unwrap()[1].contains("if-none-match: \"v1\""));
+
+        let fresh = HttpCache {
+            ttl: Some(Duration::from_secs(3600)),
+            ..HttpCache::new(&*dir)
+        };
+        assert_eq!(fetch(cached(fresh)).await.unwrap(), "a\n1\n");
+        let offline = HttpCache {
+            offline: true,
+            ..HttpCache::new(&*dir)
+        };
+        assert_eq!(fetch(cached(offline.clone())).await.unwrap(), "a\n1\n");
+        assert_eq!(requests.lock().unwrap().len(), 2);
+        let missing = base.join("missing.csv").unwrap();
+        assert!(cached(offline.clone()).fetch(&missing).await.is_err());
+
+        assert_eq!(offline.entries().unwrap()[0].url, url.as_str());
+        assert_eq!(offline.clear().unwrap(), 1);
+    }
+
+    #[test]
+    fn it_reads_retry_after() {
+        assert_eq!(retry_after("120"), Some(Duration::from_secs(120)));
+        let later = SystemTime::now() + Duration::from_secs(60);
+        let wait = retry_after(&httpdate::fmt_http_date(later)).unwrap();
+        assert!(wait <= Duration::from_secs(60) && wait > Duration::from_secs(50));
+        assert_eq!(retry_after("soon"), None);
+    }
+}
@@ -0,0 +1,289 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use ring::digest;
+use url::Url;
+
+use crate::fetcher::{Content, Metadata};
+
+/// A directory keeping the bodies of http responses along with their
+/// validators, so unchanged sources are not downloaded again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpCache {
+    pub dir: PathBuf,
+    /// how long an entry is used without revalidating it. Entries are
+    /// revalidated with a conditional request every time when `None`.
+    pub ttl: Option<Duration>,
+    /// serve cached entries, however stale, without any request, and fail
+    /// on urls that are not cached.
+    pub offline: bool,
+}
+
+/// A cached response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: Option<String>,
+    pub metadata: Metadata,
+    /// when the response was stored or last revalidated.
+    pub stored: SystemTime,
+}
+
+impl HttpCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        HttpCache {
+            dir: dir.into(),
+            ttl: None,
+            offline: false,
+        }
+    }
+
+    /// The cached responses, by url.
+    pub fn entries(&self) -> io::Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+        for entry in read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "meta")
+            {
+                entries.extend(read_entry(&path));
+            }
+        }
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+        Ok(entries)
+    }
+
+    /// Remove the entry of `url`, returning whether there was one.
+    pub fn remove(&self, url: &Url) -> io::Result<bool> {
+        let (meta, body) = self.paths(url);
+        let removed = remove_file(&meta)?;
+        remove_file(&body)?;
+        Ok(removed)
+    }
+
+    /// Remove every entry, returning how many there were.
+    pub fn clear(&self) -> io::Result<usize> {
+        let mut removed = 0;
+        for entry in read_dir(&self.dir)? {
+            let path = entry?.path();
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("meta") => {
+                    fs::remove_file(&path)?;
+                    removed += 1;
+                }
+                Some("body") => fs::remove_file(&path)?,
+                _ => {}
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Whether `entry` may be used without revalidating it.
+    pub fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        self.ttl
+            .is_some_and(|ttl| entry.stored.elapsed().is_ok_and(|elapsed| elapsed < ttl))
+    }
+
+    pub(crate) fn get(&self, url: &Url) -> io::Result<Option<CacheEntry>> {
+        let (meta, body) = self.paths(url);
+        if !meta.exists() || !body.exists() {
+            return Ok(None);
+        }
+        Ok(read_entry(&meta).filter(|entry| entry.url == url.as_str()))
+    }
+
+    /// The cached body of `entry`.
+    pub(crate) fn content(&self, entry: &CacheEntry) -> io::Result<Content> {
+        let url = Url::parse(&entry.url).map_err(io::Error::other)?;
+        let bytes = fs::read(self.paths(&url).1)?;
+        Ok(Content::from_bytes(entry.metadata.clone(), bytes))
+    }
+
+    pub(crate) fn put(
+        &self,
+        url: &Url,
+        etag: Option<String>,
+        metadata: &Metadata,
+        bytes: &Bytes,
+    ) -> io::Result<CacheEntry> {
+        fs::create_dir_all(&self.dir)?;
+        let (meta, body) = self.paths(url);
+        write_atomic(&body, bytes)?;
+        let entry = CacheEntry {
+            url: url.to_string(),
+            etag,
+            metadata: Metadata {
+                content_length: Some(bytes.len() as u64),
+                ..metadata.clone()
+            },
+            stored: SystemTime::now(),
+        };
+        write_atomic(&meta, render_entry(&entry).as_bytes())?;
+        Ok(entry)
+    }
+
+    /// Mark `entry` as revalidated now.
+    pub(crate) fn touch(&self, entry: &CacheEntry) -> io::Result<()> {
+        let url = Url::parse(&entry.url).map_err(io::Error::other)?;
+        let entry = CacheEntry {
+            stored: SystemTime::now(),
+            ..entry.clone()
+        };
+        write_atomic(&self.paths(&url).0, render_entry(&entry).as_bytes())
+    }
+
+    /// The metadata and body files of `url`, named after its sha256.
+    fn paths(&self, url: &Url) -> (PathBuf, PathBuf) {
+        let hash = digest::digest(&digest::SHA256, url.as_str().as_bytes());
+        let name: String = hash
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        (
+            self.dir.join(format!("{name}.meta")),
+            self.dir.join(format!("{name}.body")),
+        )
+    }
+}
+
+/// The entries of `dir`, none when it was not created yet.
+fn read_dir(dir: &Path) -> io::Result<Box<dyn Iterator<Item = io::Result<fs::DirEntry>>>> {
+    match fs::read_dir(dir) {
+        Ok(entries) => Ok(Box::new(entries)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Box::new(std::iter::empty())),
+        Err(e) => Err(e),
+    }
+}
+
+fn remove_file(path: &Path) -> io::Result<bool> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Write through a temporary file, so readers never see a partial file.
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    fs::write(&tmp, bytes)?;
+    fs::rename(tmp, path)
+}
+
+/// An entry as `name: value` lines, like the headers it comes from.
+fn render_entry(entry: &CacheEntry) -> String {
+    let stored = entry
+        .stored
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        .to_string();
+    let metadata = &entry.metadata;
+    let last_modified = metadata.last_modified.map(httpdate::fmt_http_date);
+    let length = metadata.content_length.map(|length| length.to_string());
+    [
+        ("url", Some(&entry.url)),
+        ("stored", Some(&stored)),
+        ("etag", entry.etag.as_ref()),
+        ("content-type", metadata.content_type.as_ref()),
+        ("content-encoding", metadata.content_encoding.as_ref()),
+        ("file-name", metadata.file_name.as_ref()),
+        ("last-modified", last_modified.as_ref()),
+        ("content-length", length.as_ref()),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Some(format!("{name}: {}\n", value?)))
+    .collect()
+}
+
+/// The entry of a metadata file, `None` when it is unreadable.
+fn read_entry(path: &Path) -> Option<CacheEntry> {
+    let content = fs::read_to_string(path).ok()?;
+    let field = |name: &str| {
+        content.lines().find_map(|line| {
+            let (key, value) = line.split_once(": ")?;
+            (key == name).then(|| value.to_owned())
+        })
+    };
+    Some(CacheEntry {
+        url: field("url")?,
+        etag: field("etag"),
+        metadata: Metadata {
+            content_type: field("content-type"),
+            content_encoding: field("content-encoding"),
+            file_name: field("file-name"),
+            last_modified: field("last-modified")
+                .and_then(|date| httpdate::parse_http_date(&date).ok()),
+            content_length: field("content-length").and_then(|length| length.parse().ok()),
+        },
+        stored: UNIX_EPOCH + Duration::from_secs(field("stored")?.parse().ok()?),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[tokio::test]
+    async fn it_stores_and_clears_entries() {
+        let dir = TempDir::new("http-cache");
+        let cache = HttpCache {
+            ttl: Some(Duration::from_secs(60)),
+            ..HttpCache::new(&*dir)
+        };
+        let url = Url::parse("https://example.com/data.csv?v=1").unwrap();
+        assert_eq!(cache.get(&url).unwrap(), None);
+        assert!(cache.entries().unwrap().is_empty());
+
+        let metadata = Metadata {
+            content_type: Some("text/csv".to_owned()),
+            file_name: Some("data.csv".to_owned()),
+            last_modified: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            ..Default::default()
+        };
+        let bytes = Bytes::from_static(b"a\n1\n");
+        let stored = cache
+            .put(&url, Some("\"v1\"".to_owned()), &metadata, &bytes)
+            .unwrap();
+        let entry = cache.get(&url).unwrap().unwrap();
+        assert_eq!(entry.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(entry.metadata.last_modified, metadata.last_modified);
+        assert_eq!(entry.metadata.content_length, Some(4));
+        assert!(cache.is_fresh(&entry));
+        assert_eq!(cache.entries().unwrap(), [entry]);
+        let content = cache.content(&stored).unwrap();
+        assert_eq!(content.bytes().await.unwrap(), bytes);
+
+        assert_eq!(cache.clear().unwrap(), 1);
+        assert!(!cache.remove(&url).unwrap());
+        assert!(cache.entries().unwrap().is_empty());
+    }
+}
@@ -19,11 +19,31 @@
 // LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
+pub mod archive;
+pub mod binder;
+pub mod compression;
 pub mod convert;
 pub mod data_set;
 pub mod dialect;
+pub mod error;
+pub mod explain;
 pub mod fetcher;
+pub mod frame_cache;
+pub mod http;
+pub mod http_cache;
 pub mod loader;
+pub mod macros;
+pub mod partition;
+pub mod s3;
+pub mod session;
+pub mod typecheck;
+pub mod udf;
+pub mod writer;
+
+#[cfg(test)]
+mod test_util;
+
+pub use error::{Error, Result};
 
 pub fn add(left: usize, right: usize) -> usize {
     left + right
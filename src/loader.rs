@@ -21,9 +21,21 @@ use std::io::Cursor;
 // LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
-use crate::data_set::DataSet;
+use crate::{compression::Compression, data_set::DataSet, fetcher::Metadata, writer::FileFormat};
 use anyhow::{Error, Ok, Result};
-use polars::io::{csv::CsvReader, SerReader};
+use bytes::Bytes;
+use polars::{
+    io::{csv::CsvReader, SerReader},
+    prelude::{JsonFormat, JsonReader, ParquetReader},
+};
+
+/// Options of the `read_csv(url, compression => 'gzip')` style reader
+/// functions, overriding what is detected from the content.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ReadOptions {
+    pub format: Option<FileFormat>,
+    pub compression: Option<Compression>,
+}
 
 pub trait Loader {
     type Error;
@@ -32,28 +44,131 @@ pub trait Loader {
 
 pub enum Load {
     Csv(CsvLoader),
+    Json(JsonLoader),
+    Parquet(ParquetLoader),
 }
 
 impl Load {
     pub fn load(self) -> Result<DataSet> {
         match self {
             Load::Csv(csv) => csv.load(),
+            Load::Json(json) => json.load(),
+            Load::Parquet(parquet) => parquet.load(),
         }
     }
 }
 
-pub struct CsvLoader(pub(crate) String);
+pub struct CsvLoader(pub(crate) Bytes);
 
 impl Loader for CsvLoader {
     type Error = Error;
     fn load(self) -> Result<DataSet, Self::Error> {
         let df = CsvReader::new(Cursor::new(self.0))
             .infer_schema(Some(16))
+            .with_try_parse_dates(true)
+            .finish()?;
+        Ok(DataSet(df))
+    }
+}
+
+/// A json array of objects, or one object per line.
+pub struct JsonLoader(pub(crate) Bytes, pub(crate) JsonFormat);
+
+impl Loader for JsonLoader {
+    type Error = Error;
+    fn load(self) -> Result<DataSet, Self::Error> {
+        let df = JsonReader::new(Cursor::new(self.0))
+            .with_json_format(self.1)
             .finish()?;
         Ok(DataSet(df))
     }
 }
 
-pub fn detect_content(content: String) -> Load {
-    Load::Csv(CsvLoader(content))
+pub struct ParquetLoader(pub(crate) Bytes);
+
+impl Loader for ParquetLoader {
+    type Error = Error;
+    fn load(self) -> Result<DataSet, Self::Error> {
+        let df = ParquetReader::new(Cursor::new(self.0)).finish()?;
+        Ok(DataSet(df))
+    }
+}
+
+/// Pick a loader for `format`, or detect it from the content type, then the
+/// file extension and finally the first bytes of the content, falling back to
+/// csv. Json arrays and json lines are told apart by their first byte.
+pub fn detect_content(content: Bytes, metadata: &Metadata, format: Option<FileFormat>) -> Load {
+    let array = content.trim_ascii_start().starts_with(b"[");
+    match format {
+        Some(FileFormat::Csv) => return Load::Csv(CsvLoader(content)),
+        Some(FileFormat::Parquet) => return Load::Parquet(ParquetLoader(content)),
+        Some(FileFormat::NdJson) if array => {
+            return Load::Json(JsonLoader(content, JsonFormat::Json))
+        }
+        Some(FileFormat::NdJson) => return Load::Json(JsonLoader(content, JsonFormat::JsonLines)),
+        None => {}
+    }
+    let content_type = metadata.content_type.as_deref().unwrap_or_default();
+    let extension = metadata
+        .file_name
+        .as_deref()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default();
+    let json_lines = || Load::Json(JsonLoader(content.clone(), JsonFormat::JsonLines));
+    match (content_type, extension.as_str()) {
+        (t, _) if t.contains("parquet") => Load::Parquet(ParquetLoader(content)),
+        ("application/x-ndjson" | "application/jsonl", _) => json_lines(),
+        ("application/json", _) => Load::Json(JsonLoader(content, JsonFormat::Json)),
+        (_, "parquet") => Load::Parquet(ParquetLoader(content)),
+        (_, "ndjson" | "jsonl") => json_lines(),
+        (_, "json") => Load::Json(JsonLoader(content, JsonFormat::Json)),
+        _ if content.starts_with(b"PAR1") => Load::Parquet(ParquetLoader(content)),
+        _ if array => Load::Json(JsonLoader(content, JsonFormat::Json)),
+        _ if content.trim_ascii_start().starts_with(b"{") => json_lines(),
+        _ => Load::Csv(CsvLoader(content)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn named(file_name: &str) -> Metadata {
+        Metadata {
+            file_name: Some(file_name.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn it_detects_the_format() {
+        let lines = Bytes::from_static(b"{\"a\": 1}\n{\"a\": 2}\n");
+        let df = detect_content(lines.clone(), &named("a.jsonl"), None)
+            .load()
+            .unwrap();
+        assert_eq!(df.shape(), (2, 1));
+        let df = detect_content(lines, &Metadata::default(), None)
+            .load()
+            .unwrap();
+        assert_eq!(df.shape(), (2, 1));
+
+        let csv = Bytes::from_static(b"a,b\n1,2\n");
+        assert!(matches!(
+            detect_content(csv.clone(), &named("a.txt"), None),
+            Load::Csv(_)
+        ));
+        let metadata = Metadata {
+            content_type: Some("application/json".to_owned()),
+            ..named("a.csv")
+        };
+        assert!(matches!(
+            detect_content(csv.clone(), &metadata, None),
+            Load::Json(_)
+        ));
+        assert!(matches!(
+            detect_content(csv, &metadata, Some(FileFormat::Csv)),
+            Load::Csv(_)
+        ));
+    }
 }
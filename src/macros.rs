@@ -0,0 +1,322 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::{collections::HashMap, ops::ControlFlow};
+
+use sqlparser::ast::{
+    visit_expressions_mut, visit_relations_mut, Expr as SqlExpr, FunctionArgExpr, Ident, MacroArg,
+    Statement, Value,
+};
+
+use crate::{
+    convert::function_args,
+    error::{Error, Result},
+};
+
+/// How deep macros may expand into other macros, guards against recursion.
+const MAX_DEPTH: usize = 16;
+
+/// A `CREATE MACRO` expression with its parameters, expanded in place of
+/// every call before the statement is bound.
+#[derive(Debug, Clone)]
+pub struct Macro {
+    params: Vec<MacroArg>,
+    body: SqlExpr,
+}
+
+impl Macro {
+    pub fn new(params: Vec<MacroArg>, body: SqlExpr) -> Self {
+        Macro { params, body }
+    }
+
+    /// The body with every parameter replaced by its argument, or default.
+    fn apply(&self, name: &str, args: Vec<SqlExpr>, snippet: &str) -> Result<SqlExpr> {
+        let required = self
+            .params
+            .iter()
+            .filter(|p| p.default_expr.is_none())
+            .count();
+        if args.len() < required || args.len() > self.params.len() {
+            return Err(Error::type_mismatch(
+                format!(
+                    "{name} expects {} arguments, got {}",
+                    self.params.len(),
+                    args.len()
+                ),
+                snippet,
+            ));
+        }
+        let mut args = args.into_iter();
+        let values: Vec<_> = self
+            .params
+            .iter()
+            .map(|p| args.next().or_else(|| p.default_expr.clone()))
+            .collect();
+
+        let mut body = self.body.clone();
+        let _ = visit_expressions_mut(&mut body, |e| {
+            if let SqlExpr::Identifier(id) = e {
+                let param = self.params.iter().position(|p| same_name(&p.name, id));
+                if let Some(value) = param.and_then(|i| values[i].clone()) {
+                    *e = nested(value);
+                }
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        Ok(nested(body))
+    }
+}
+
+/// Expands `${variable}` references and macro calls of a statement.
+///
+/// A reference making up a whole expression is replaced by the value of the
+/// variable, references inside identifiers (e.g. a url) are replaced by its
+/// text.
+pub struct Expander<'a> {
+    variables: &'a HashMap<String, SqlExpr>,
+    macros: &'a HashMap<String, Macro>,
+}
+
+impl<'a> Expander<'a> {
+    pub fn new(
+        variables: &'a HashMap<String, SqlExpr>,
+        macros: &'a HashMap<String, Macro>,
+    ) -> Self {
+        Expander { variables, macros }
+    }
+
+    pub fn expand(&self, statement: &mut Statement) -> Result<()> {
+        let flow = visit_relations_mut(statement, |name| {
+            for ident in name.0.iter_mut() {
+                if let Err(e) = self.substitute(&mut ident.value) {
+                    return ControlFlow::Break(e);
+                }
+            }
+            ControlFlow::Continue(())
+        });
+        if let ControlFlow::Break(e) = flow {
+            return Err(e);
+        }
+        // every pass expands the calls left over in the bodies of the previous one.
+        let mut last = None;
+        for _ in 0..MAX_DEPTH {
+            let mut expanded = None;
+            let flow = visit_expressions_mut(statement, |e| match self.rewrite(e) {
+                Ok(call) => {
+                    expanded = call.or(expanded.take());
+                    ControlFlow::Continue(())
+                }
+                Err(e) => ControlFlow::Break(e),
+            });
+            if let ControlFlow::Break(e) = flow {
+                return Err(e);
+            }
+            match expanded {
+                None => return Ok(()),
+                call => last = call,
+            }
+        }
+        Err(Error::unsupported(
+            "recursive macro",
+            last.unwrap_or_default(),
+        ))
+    }
+
+    /// Rewrite a variable reference or macro call, returning the expanded call.
+    fn rewrite(&self, expr: &mut SqlExpr) -> Result<Option<String>> {
+        match expr {
+            SqlExpr::Identifier(id) if id.value.contains("${") => {
+                match variable_name(&id.value).map(|name| self.variable(name)) {
+                    Some(value) => *expr = value?.clone(),
+                    None => self.substitute(&mut id.value)?,
+                }
+                Ok(None)
+            }
+            SqlExpr::Function(f) if f.over.is_none() => {
+                let name = f.name.to_string().to_lowercase();
+                let Some(definition) = self.macros.get(&name) else {
+                    return Ok(None);
+                };
+                let snippet = f.to_string();
+                let args = function_args(f)?
+                    .into_iter()
+                    .map(|arg| match arg {
+                        FunctionArgExpr::Expr(e) => Ok(e.clone()),
+                        arg => Err(Error::unsupported(format!("arguments of {name}"), arg)),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                *expr = definition.apply(&name, args, &snippet)?;
+                Ok(Some(snippet))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Replace every `${variable}` inside `text` by the text of its value.
+    fn substitute(&self, text: &mut String) -> Result<()> {
+        while let Some(start) = text.find("${") {
+            let Some(end) = text[start..].find('}').map(|end| start + end) else {
+                break;
+            };
+            let value = match self.variable(&text[start + 2..end])? {
+                SqlExpr::Value(Value::SingleQuotedString(s) | Value::DoubleQuotedString(s)) => {
+                    s.clone()
+                }
+                SqlExpr::Value(Value::Number(n, _)) => n.clone(),
+                SqlExpr::Identifier(id) => id.value.clone(),
+                value => value.to_string(),
+            };
+            text.replace_range(start..=end, &value);
+        }
+        Ok(())
+    }
+
+    fn variable(&self, name: &str) -> Result<&SqlExpr> {
+        self.variables
+            .get(&name.to_lowercase())
+            .ok_or_else(|| Error::catalog("is not a defined variable", format!("${{{name}}}")))
+    }
+}
+
+/// `name` when `text` is exactly `${name}`.
+fn variable_name(text: &str) -> Option<&str> {
+    text.strip_prefix("${")?
+        .strip_suffix('}')
+        .filter(|name| !name.contains(['$', '{', '}']))
+}
+
+fn same_name(param: &Ident, id: &Ident) -> bool {
+    match id.quote_style {
+        Some(_) => param.value == id.value,
+        None => param.value.eq_ignore_ascii_case(&id.value),
+    }
+}
+
+/// Parenthesise `expr` unless it is atomic, so operator precedence survives.
+fn nested(expr: SqlExpr) -> SqlExpr {
+    match expr {
+        SqlExpr::Identifier(_)
+        | SqlExpr::Value(_)
+        | SqlExpr::Nested(_)
+        | SqlExpr::Function(_)
+        | SqlExpr::Cast { .. } => expr,
+        expr => SqlExpr::Nested(Box::new(expr)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dialect::OrinDialect;
+    use crate::{error::Span, session::Session, test_util::covid};
+    use sqlparser::{
+        ast::{SelectItem, SetExpr},
+        parser::Parser,
+    };
+
+    fn parse(sql: &str) -> Statement {
+        Parser::parse_sql(&OrinDialect, sql).unwrap().remove(0)
+    }
+
+    fn expr(sql: &str) -> SqlExpr {
+        match parse(&format!("select {sql}")) {
+            Statement::Query(q) => match *q.body {
+                SetExpr::Select(s) => match &s.projection[0] {
+                    SelectItem::UnnamedExpr(e) => e.clone(),
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn it_expands_variables_and_macros() {
+        let variables = HashMap::from([
+            ("year".to_owned(), expr("2024")),
+            ("country".to_owned(), expr("'France'")),
+        ]);
+        let macros = HashMap::from([(
+            "per_million".to_owned(),
+            Macro::new(
+                vec![MacroArg::new("x"), MacroArg::new("pop")],
+                expr("x * 1e6 / pop"),
+            ),
+        )]);
+        let mut statement = parse(
+            "select per_million(new_cases + 1, population) from file:///data/${year}.csv \
+            where location = ${country}",
+        );
+        Expander::new(&variables, &macros)
+            .expand(&mut statement)
+            .unwrap();
+        assert_eq!(
+            statement.to_string(),
+            "SELECT ((new_cases + 1) * 1e6 / population) FROM file:///data/2024.csv \
+            WHERE location = 'France'"
+        );
+    }
+
+    #[test]
+    fn it_reports_undefined_variables_and_recursion() {
+        let macros = HashMap::from([(
+            "loop".to_owned(),
+            Macro::new(vec![MacroArg::new("x")], expr("loop(x) + 1")),
+        )]);
+        let variables = HashMap::new();
+        let expander = Expander::new(&variables, &macros);
+        let err = expander
+            .expand(&mut parse("select ${missing} from t"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "`${missing}` is not a defined variable");
+        let err = expander
+            .expand(&mut parse("select loop(a) from t"))
+            .unwrap_err();
+        assert!(matches!(err, Error::UnsupportedFeature { .. }));
+    }
+
+    #[tokio::test]
+    async fn it_expands_them_in_a_session() {
+        let mut session = Session::default();
+        let script = format!(
+            "set source = '{}'; \
+            set min_cases = 1000; \
+            create macro per_million(x, pop) as x * 1e6 / pop; \
+            select location, per_million(new_cases, population) rate from ${{source}} \
+            where new_cases > ${{min_cases}} order by new_cases desc limit 3",
+            covid()
+        );
+        let results = session.run_script(script).await.unwrap();
+        assert_eq!(results.len(), 4);
+        let ds = results[3].as_ref().unwrap();
+        assert_eq!(ds.get_column_names(), ["location", "rate"]);
+        assert_eq!(ds.height(), 3);
+
+        let err = session
+            .query("create macro per_million(x) as x")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::CatalogError { .. }));
+        let err = session.query("select a from ${missing}").await.unwrap_err();
+        assert_eq!(err.span(), Some(Span { start: 14, end: 24 }));
+    }
+}
@@ -0,0 +1,333 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use percent_encoding::percent_decode_str;
+use polars::prelude::*;
+use url::Url;
+
+use crate::error::Result;
+
+/// The value hive writes for a null partition key.
+const DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// The hive style `key=value` directories of each of the `urls` listed for
+/// the directory or glob pattern `source`, one row per url and one column per
+/// key. Only the directories below the table root are partitions. A key is
+/// typed as integers or floats when all its values parse as such, as strings
+/// otherwise. `None` when no url is partitioned.
+pub fn partitions(source: &str, urls: &[String]) -> Result<Option<DataFrame>> {
+    let root = root(source);
+    let parsed: Vec<Vec<(String, String)>> = urls
+        .iter()
+        .map(|url| segments(url.strip_prefix(root.as_str()).unwrap_or(url)))
+        .collect();
+    let mut keys: Vec<&str> = Vec::new();
+    for (key, _) in parsed.iter().flatten() {
+        if !keys.contains(&key.as_str()) {
+            keys.push(key);
+        }
+    }
+    if keys.is_empty() {
+        return Ok(None);
+    }
+    let columns = keys
+        .iter()
+        .map(|key| {
+            let values: Vec<Option<&str>> = parsed
+                .iter()
+                .map(|segments| {
+                    segments
+                        .iter()
+                        .find(|(name, _)| name == key)
+                        .map(|(_, value)| value.as_str())
+                        .filter(|value| *value != DEFAULT_PARTITION)
+                })
+                .collect();
+            typed(key, &values)
+        })
+        .collect();
+    Ok(Some(DataFrame::new(columns)?))
+}
+
+/// Which partitions may hold rows matching `condition`. Only the conjuncts
+/// of `condition` referring to nothing but partition columns are evaluated,
+/// so every partition is kept when there are none, or they fail to evaluate.
+pub fn prune(partitions: &DataFrame, condition: &Expr) -> Vec<bool> {
+    let everything = vec![true; partitions.height()];
+    let names = partitions.get_column_names();
+    let predicate = conjuncts(condition)
+        .into_iter()
+        .filter(|expr| {
+            let columns: Vec<_> = expr
+                .into_iter()
+                .filter_map(|node| match node {
+                    Expr::Column(name) => Some(name),
+                    _ => None,
+                })
+                .collect();
+            !columns.is_empty() && columns.iter().all(|name| names.contains(&name.as_ref()))
+        })
+        .cloned()
+        .reduce(|left, right| left.and(right));
+    let Some(predicate) = predicate else {
+        return everything;
+    };
+    let keep = partitions
+        .clone()
+        .lazy()
+        .select([predicate.alias("keep")])
+        .collect();
+    match keep
+        .as_ref()
+        .map(|df| df.column("keep").map(|keep| keep.bool()))
+    {
+        Ok(Ok(Ok(mask))) if mask.len() == partitions.height() => {
+            mask.into_iter().map(|keep| keep.unwrap_or(false)).collect()
+        }
+        _ => everything,
+    }
+}
+
+/// The directory `source`, or the directories of a glob pattern before its
+/// first wildcard, spelled as the listed urls are.
+fn root(source: &str) -> String {
+    let root = match source.find(['*', '?', '[']) {
+        Some(wildcard) => {
+            let end = source[..wildcard].rfind(['/', '#']).map_or(0, |i| i + 1);
+            &source[..end]
+        }
+        None => source,
+    };
+    Url::parse(root).map_or_else(|_| root.to_owned(), String::from)
+}
+
+/// The `key=value` directories of the `path` below a table root, and of the
+/// member path of an archive url.
+fn segments(path: &str) -> Vec<(String, String)> {
+    let mut segments: Vec<&str> = path.split(['/', '#']).collect();
+    segments.pop();
+    segments
+        .into_iter()
+        .filter_map(|segment| segment.split_once('='))
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, value)| (decode(key), decode(value)))
+        .collect()
+}
+
+fn decode(text: &str) -> String {
+    percent_decode_str(text).decode_utf8_lossy().into_owned()
+}
+
+fn typed(name: &str, values: &[Option<&str>]) -> Series {
+    let parsed = |parse: fn(&str) -> bool| values.iter().flatten().all(|value| parse(value));
+    if parsed(|value| value.parse::<i64>().is_ok()) {
+        let values: Vec<Option<i64>> = values
+            .iter()
+            .map(|value| value.and_then(|value| value.parse().ok()))
+            .collect();
+        Series::new(name, values)
+    } else if parsed(|value| value.parse::<f64>().is_ok()) {
+        let values: Vec<Option<f64>> = values
+            .iter()
+            .map(|value| value.and_then(|value| value.parse().ok()))
+            .collect();
+        Series::new(name, values)
+    } else {
+        Series::new(name, values)
+    }
+}
+
+/// The operands of the top level `AND`s of `expr`.
+fn conjuncts(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::BinaryExpr {
+            left,
+            op: Operator::And,
+            right,
+        } => {
+            let mut operands = conjuncts(left);
+            operands.extend(conjuncts(right));
+            operands
+        }
+        expr => vec![expr],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{error::BoxError, fetcher::Fetcher, session::Session, test_util::TempDir};
+
+    fn urls() -> Vec<String> {
+        [
+            "year=2023/month=12",
+            "year=2024/month=01",
+            "year=2024/month=02",
+        ]
+        .iter()
+        .map(|dir| format!("file:///data/{dir}/part.csv"))
+        .collect()
+    }
+
+    #[test]
+    fn it_types_partition_values() {
+        let df = partitions("file:///data", &urls()).unwrap().unwrap();
+        assert_eq!(df.get_column_names(), ["year", "month"]);
+        assert_eq!(df.column("month").unwrap().dtype(), &DataType::Int64);
+        assert_eq!(df.column("month").unwrap().i64().unwrap().get(1), Some(1));
+
+        let urls = ["file:///a/region=eu%20west/x.csv", "file:///a/x.csv"].map(String::from);
+        let df = partitions("file:///a/*.csv", &urls).unwrap().unwrap();
+        let region = df.column("region").unwrap().str().unwrap();
+        assert_eq!(region.get(0), Some("eu west"));
+        assert_eq!(region.get(1), None);
+        assert!(partitions("file:///a", &["file:///a/x.csv".to_owned()])
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn it_prunes_partitions() {
+        let df = partitions("file:///data", &urls()).unwrap().unwrap();
+        let condition = col("year")
+            .eq(lit(2024))
+            .and(col("month").gt(lit(1)))
+            .and(col("cases").gt(lit(10)));
+        assert_eq!(prune(&df, &condition), [false, false, true]);
+        assert_eq!(prune(&df, &col("cases").gt(lit(10))), [true, true, true]);
+        assert_eq!(prune(&df, &col("year").eq(lit("2024"))), [true, true, true]);
+    }
+
+    #[test]
+    fn it_only_parses_directories_below_the_root() {
+        let urls = [
+            "file:///k=v/data/day=1/a.csv",
+            "file:///k=v/data/day=2/b.csv",
+        ];
+        let urls = urls.map(String::from);
+        let df = partitions("file:///k=v/data", &urls).unwrap().unwrap();
+        assert_eq!(df.get_column_names(), ["day"]);
+        let df = partitions("file:///k=v/data/day=*/*.csv", &urls)
+            .unwrap()
+            .unwrap();
+        assert_eq!(df.get_column_names(), ["day"]);
+        // the table root itself is a partition directory.
+        let urls = ["file:///data/day=1/a.csv".to_owned()];
+        assert!(partitions("file:///data/day=1", &urls).unwrap().is_none());
+        let urls = ["file:///data/day%201=1/a.csv".to_owned()];
+        assert!(partitions("file:///data/day 1=1", &urls).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn it_prunes_hive_partitions() {
+        let dir = TempDir::new("hive");
+        for (partition, content) in [
+            ("year=2024/month=01", "cases\n1\n2\n"),
+            ("year=2024/month=02", "cases\n3\n"),
+        ] {
+            std::fs::create_dir_all(dir.join(partition)).unwrap();
+            std::fs::write(dir.join(partition).join("part.csv"), content).unwrap();
+        }
+        // not valid parquet: the query fails if this partition is fetched.
+        std::fs::create_dir_all(dir.join("year=2025/month=01")).unwrap();
+        std::fs::write(dir.join("year=2025/month=01/part.parquet"), "cases\n0\n").unwrap();
+
+        let mut session = Session::default();
+        let sql = format!(
+            "select year, month, sum(cases) total from 'file://{}' where year = 2024 and cases > 0 group by year, month order by month",
+            dir.display()
+        );
+        let ds = session.query(sql).await.unwrap();
+        assert_eq!(ds.column("month").unwrap().dtype(), &DataType::Int64);
+        assert_eq!(
+            ds.column("total").unwrap().i64().unwrap().to_vec(),
+            [Some(3), Some(3)]
+        );
+        let sql = format!("select * from 'file://{}' where year < 2024", dir.display());
+        let ds = session.query(sql).await.unwrap();
+        assert_eq!(ds.get_column_names(), ["cases", "year", "month"]);
+        assert_eq!(ds.height(), 0);
+        // pruned with the names the binder resolves.
+        let sql = format!(
+            "select CASES from 'file://{}' where Year = 2024",
+            dir.display()
+        );
+        assert_eq!(session.query(sql).await.unwrap().height(), 3);
+        assert!(session
+            .query(format!("select * from 'file://{}'", dir.display()))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn it_fetches_nothing_when_every_partition_is_pruned() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use async_trait::async_trait;
+        use url::Url;
+
+        use crate::fetcher::Content;
+
+        /// Two hive partitions, counting the files fetched.
+        #[derive(Clone, Default)]
+        struct Counted(Arc<AtomicUsize>);
+
+        #[async_trait]
+        impl Fetcher for Counted {
+            type Error = BoxError;
+
+            async fn fetch(&self, _: &Url) -> std::result::Result<Content, BoxError> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(Content::from_bytes(Default::default(), "cases\n1\n"))
+            }
+
+            async fn list(&self, _: &Url) -> std::result::Result<Vec<Url>, BoxError> {
+                let urls = ["year=2024/part.csv", "year=2025/part.csv"]
+                    .map(|name| Url::parse(&format!("counted://data/{name}")).unwrap());
+                Ok(urls.to_vec())
+            }
+        }
+
+        let counted = Counted::default();
+        let mut session = Session::default();
+        session.register_fetcher("counted", counted.clone());
+        let ds = session
+            .query("select * from 'counted://data/' where year < 2024")
+            .await
+            .unwrap();
+        assert_eq!(ds.get_column_names(), ["year"]);
+        assert_eq!(ds.height(), 0);
+        assert_eq!(counted.0.load(Ordering::SeqCst), 0);
+
+        // the columns of a file loaded before are known without fetching it.
+        session
+            .query("select * from 'counted://data/' where year = 2025")
+            .await
+            .unwrap();
+        assert_eq!(counted.0.load(Ordering::SeqCst), 1);
+        let ds = session
+            .query("select * from 'counted://data/' where year < 2024")
+            .await
+            .unwrap();
+        assert_eq!(ds.get_column_names(), ["cases", "year"]);
+        assert_eq!(counted.0.load(Ordering::SeqCst), 1);
+    }
+}
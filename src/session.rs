@@ -0,0 +1,1394 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures_util::{stream, StreamExt, TryStreamExt};
+
+use polars::prelude::*;
+use polars_core::utils::get_supertype;
+use sqlparser::{
+    ast::{Expr as SqlExpr, Statement},
+    parser::Parser,
+};
+use tracing::{debug, info};
+
+use crate::{
+    binder::{suggest, Binder},
+    compression::decompress,
+    convert::{Command, Sql, TableKind},
+    data_set::{DataSet, OnError, QueryOptions},
+    dialect::OrinDialect,
+    error::{BoxError, Error, Result},
+    explain::{analyze, explain, Profile, Timing},
+    fetcher::{
+        is_local, local_path, source_url, Content, Fetcher, FetcherRegistry, Metadata, STDIN,
+    },
+    frame_cache::{fingerprint, FrameCache, FrameKey},
+    loader::{detect_content, ReadOptions},
+    macros::{Expander, Macro},
+    partition::{partitions, prune},
+    typecheck::TypeChecker,
+    udf::{AggregateUdf, FunctionRegistry, ScalarUdf},
+    writer::{check_append, FileWriter, WriteOptions, Writer},
+};
+
+/// The column holding the HAVING condition of each group while aggregating.
+const HAVING: &str = "__having";
+
+/// The column telling which file the rows of a glob or directory source come
+/// from. It is left out of `*`.
+pub const SOURCE_FILE: &str = "_source_file";
+
+/// The urls loaded while executing a script, by url and reader options.
+type Sources = HashMap<(String, ReadOptions), DataFrame>;
+
+/// The decompressed content of a file, read into memory.
+struct Fetched {
+    url: String,
+    metadata: Metadata,
+    bytes: Bytes,
+    elapsed: Duration,
+}
+
+async fn fetch_file(
+    fetchers: &FetcherRegistry,
+    url: &str,
+    options: &ReadOptions,
+) -> Result<Fetched> {
+    info!("retrieving data from {url}");
+    let start = Instant::now();
+    let content = fetchers.fetch(url).await?;
+    let fetched = read(url, content, options).await?;
+    Ok(Fetched {
+        elapsed: start.elapsed(),
+        ..fetched
+    })
+}
+
+/// Decompress `content` and read it into memory.
+async fn read(url: &str, content: Content, options: &ReadOptions) -> Result<Fetched> {
+    let start = Instant::now();
+    let content = decompress(content, options.compression)
+        .await
+        .map_err(|e| Error::fetch(url, e))?;
+    let metadata = content.metadata.clone();
+    let bytes = content.bytes().await.map_err(|e| Error::fetch(url, e))?;
+    Ok(Fetched {
+        url: url.to_owned(),
+        metadata,
+        bytes,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// What a table name registered in a [`Session`] refers to.
+#[derive(Clone)]
+pub enum TableSource {
+    /// a url, fetched and loaded the first time the table is queried.
+    Url(String),
+    /// data which is already loaded.
+    DataFrame(DataFrame),
+    /// csv, json or parquet content held in memory, loaded the first time
+    /// the table is queried.
+    Content(Bytes, ReadOptions),
+    /// the unexecuted plan of a `CREATE VIEW`.
+    View(Box<LazyFrame>),
+}
+
+impl TableSource {
+    pub fn kind(&self) -> TableKind {
+        match self {
+            TableSource::View(_) => TableKind::View,
+            _ => TableKind::Table,
+        }
+    }
+}
+
+impl fmt::Debug for TableSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableSource::Url(url) => f.debug_tuple("Url").field(url).finish(),
+            TableSource::DataFrame(df) => f.debug_tuple("DataFrame").field(df).finish(),
+            TableSource::Content(bytes, options) => f
+                .debug_tuple("Content")
+                .field(&format_args!("{} bytes", bytes.len()))
+                .field(options)
+                .finish(),
+            TableSource::View(lf) => f.debug_tuple("View").field(&lf.describe_plan()).finish(),
+        }
+    }
+}
+
+impl From<&str> for TableSource {
+    fn from(url: &str) -> Self {
+        TableSource::Url(url.to_owned())
+    }
+}
+
+impl From<String> for TableSource {
+    fn from(url: String) -> Self {
+        TableSource::Url(url)
+    }
+}
+
+impl From<DataFrame> for TableSource {
+    fn from(df: DataFrame) -> Self {
+        TableSource::DataFrame(df)
+    }
+}
+
+impl From<DataSet> for TableSource {
+    fn from(ds: DataSet) -> Self {
+        TableSource::DataFrame(ds.0)
+    }
+}
+
+/// A catalog of named tables plus the options queries are executed with.
+///
+/// ```no_run
+/// # async fn run() -> sqltools::Result<()> {
+/// use sqltools::session::Session;
+///
+/// let mut session = Session::default();
+/// session.register_table("covid", "file:///data/owid-covid-latest.csv");
+/// let top = session.query("select location from covid order by new_cases desc limit 5").await?;
+/// let deaths = session.query("select location from covid where new_deaths > 500").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Session {
+    catalog: HashMap<String, TableSource>,
+    functions: FunctionRegistry,
+    fetchers: FetcherRegistry,
+    /// the parsed content of the files fetched by earlier queries, possibly
+    /// shared with other sessions.
+    frames: Arc<Mutex<FrameCache>>,
+    /// values of `SET` variables and `CREATE MACRO` definitions, by lowercase name.
+    variables: HashMap<String, SqlExpr>,
+    macros: HashMap<String, Macro>,
+    options: QueryOptions,
+    /// set while planning the statement of an `EXPLAIN`.
+    profile: Option<Profile>,
+}
+
+impl Session {
+    pub fn new(options: QueryOptions) -> Self {
+        Session {
+            catalog: HashMap::new(),
+            functions: FunctionRegistry::default(),
+            fetchers: FetcherRegistry::default(),
+            frames: Arc::default(),
+            variables: HashMap::new(),
+            macros: HashMap::new(),
+            options,
+            profile: None,
+        }
+    }
+
+    pub fn options(&self) -> &QueryOptions {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut QueryOptions {
+        &mut self.options
+    }
+
+    /// Register `source` under `name`, replacing any table with the same name.
+    /// Urls are loaded lazily and then shared by every following query.
+    pub fn register_table(&mut self, name: impl Into<String>, source: impl Into<TableSource>) {
+        self.catalog.insert(name.into(), source.into());
+    }
+
+    pub fn register_dataframe(&mut self, name: impl Into<String>, df: DataFrame) {
+        self.register_table(name, TableSource::DataFrame(df));
+    }
+
+    /// Register in memory csv, json or parquet `content`, e.g. a `String`, a
+    /// `Vec<u8>` or a borrowed `&[u8]`, copied and read with `options` the
+    /// first time it is queried.
+    pub fn register_content(
+        &mut self,
+        name: impl Into<String>,
+        content: impl AsRef<[u8]>,
+        options: ReadOptions,
+    ) {
+        let content = Bytes::copy_from_slice(content.as_ref());
+        self.register_table(name, TableSource::Content(content, options));
+    }
+
+    /// Register a rust closure as the sql scalar function `name`. It is called
+    /// with one series per argument, `args` and `return_type` declare the
+    /// types used while type checking calls.
+    ///
+    /// ```no_run
+    /// # async fn run() -> sqltools::Result<()> {
+    /// use polars::prelude::*;
+    /// use sqltools::session::Session;
+    ///
+    /// let mut session = Session::default();
+    /// session.register_udf("shout", vec![DataType::String], DataType::String, |series| {
+    ///     Ok(series[0].str()?.to_uppercase().into_series())
+    /// });
+    /// session.query("select shout(location) from covid").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_udf<F>(
+        &mut self,
+        name: impl Into<String>,
+        args: Vec<DataType>,
+        return_type: DataType,
+        fun: F,
+    ) where
+        F: Fn(&[Series]) -> PolarsResult<Series> + Send + Sync + 'static,
+    {
+        self.functions
+            .register_scalar(ScalarUdf::new(name, args, return_type, fun));
+    }
+
+    /// Register a rust closure as the sql aggregate function `name`. It is
+    /// called once per group, or per partition when used in a window, and
+    /// returns a series holding the single aggregated value.
+    ///
+    /// ```no_run
+    /// # async fn run() -> sqltools::Result<()> {
+    /// use polars::prelude::*;
+    /// use sqltools::session::Session;
+    ///
+    /// let mut session = Session::default();
+    /// session.register_udaf(
+    ///     "weighted_avg",
+    ///     vec![DataType::Float64, DataType::Float64],
+    ///     DataType::Float64,
+    ///     |series| {
+    ///         let weights = series[1].sum::<f64>()?;
+    ///         let total = (&series[0] * &series[1]).sum::<f64>()?;
+    ///         Ok(Series::new("weighted_avg", [total / weights]))
+    ///     },
+    /// );
+    /// session
+    ///     .query(
+    ///         "select continent, weighted_avg(new_cases_per_million, population) \
+    ///         from covid group by continent",
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_udaf<F>(
+        &mut self,
+        name: impl Into<String>,
+        args: Vec<DataType>,
+        return_type: DataType,
+        fun: F,
+    ) where
+        F: Fn(&[Series]) -> PolarsResult<Series> + Send + Sync + 'static,
+    {
+        self.functions
+            .register_aggregate(AggregateUdf::new(name, args, return_type, fun));
+    }
+
+    /// Remove a scalar or aggregate function, returning whether it was registered.
+    pub fn deregister_udf(&mut self, name: &str) -> bool {
+        self.functions.deregister(name)
+    }
+
+    /// Fetch the urls of `scheme` with a custom fetcher, replacing the
+    /// fetcher previously registered for it.
+    pub fn register_fetcher<F>(&mut self, scheme: &str, fetcher: F)
+    where
+        F: Fetcher<Error = BoxError> + 'static,
+    {
+        self.fetchers.register(scheme, fetcher);
+    }
+
+    pub fn fetchers_mut(&mut self) -> &mut FetcherRegistry {
+        &mut self.fetchers
+    }
+
+    /// The cache of parsed files, e.g. to change its budget or invalidate a
+    /// source.
+    pub fn frame_cache(&self) -> MutexGuard<'_, FrameCache> {
+        self.frames.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Reuse the files parsed by the other sessions sharing `frames`, in
+    /// place of a cache of its own.
+    pub fn with_frame_cache(mut self, frames: Arc<Mutex<FrameCache>>) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    /// Remove a table from the catalog, returning whether it was registered.
+    pub fn deregister_table(&mut self, name: &str) -> bool {
+        self.catalog.remove(name).is_some()
+    }
+
+    /// The registered table names, in no particular order.
+    pub fn table_names(&self) -> impl Iterator<Item = &str> {
+        self.catalog.keys().map(|name| name.as_str())
+    }
+
+    pub async fn query<T: AsRef<str>>(&mut self, sql: T) -> Result<DataSet> {
+        let sql = sql.as_ref();
+        let execute = async {
+            let mut ast = parse(sql)?;
+            if ast.is_empty() {
+                return Err(Error::ParseError {
+                    message: "no statement to execute".to_owned(),
+                    position: None,
+                    span: None,
+                });
+            }
+            if ast.len() > 1 {
+                return Err(Error::unsupported(
+                    "multiple statements in query, use run_script",
+                    &ast[1],
+                ));
+            }
+            self.execute(ast.remove(0), &mut HashMap::new()).await
+        };
+        execute.await.map_err(|e| e.locate(sql))
+    }
+
+    /// Execute the semicolon separated statements of `sql` in order, returning
+    /// one result per executed statement, see [`OnError`]. Urls referenced
+    /// inline are only fetched once per script.
+    ///
+    /// The outer error is reserved for scripts which can not be parsed at all.
+    pub async fn run_script<T: AsRef<str>>(&mut self, sql: T) -> Result<Vec<Result<DataSet>>> {
+        let sql = sql.as_ref();
+        let ast = parse(sql).map_err(|e| e.locate(sql))?;
+        let mut sources = HashMap::new();
+        let mut results = Vec::with_capacity(ast.len());
+        for statement in ast {
+            let result = self
+                .execute(statement, &mut sources)
+                .await
+                .map_err(|e| e.locate(sql));
+            let failed = result.is_err();
+            results.push(result);
+            if failed && self.options.on_error == OnError::Stop {
+                break;
+            }
+        }
+        Ok(results)
+    }
+
+    async fn execute(
+        &mut self,
+        mut statement: Statement,
+        sources: &mut Sources,
+    ) -> Result<DataSet> {
+        debug!("executing {statement}");
+        Expander::new(&self.variables, &self.macros).expand(&mut statement)?;
+        match Command::try_from(statement)? {
+            Command::Query(query) => Ok(DataSet(self.plan(query, sources).await?.collect()?)),
+            Command::CreateView {
+                name,
+                query,
+                or_replace,
+                if_not_exists,
+            } => {
+                if self.ensure_creatable(&name, or_replace, if_not_exists)? {
+                    let plan = self.plan(query, sources).await?;
+                    self.catalog.insert(name, TableSource::View(Box::new(plan)));
+                }
+                Ok(DataSet(DataFrame::empty()))
+            }
+            Command::CreateTable {
+                name,
+                query,
+                or_replace,
+                if_not_exists,
+            } => {
+                if self.ensure_creatable(&name, or_replace, if_not_exists)? {
+                    let df = self.plan(query, sources).await?.collect()?;
+                    self.catalog.insert(name, TableSource::DataFrame(df));
+                }
+                Ok(DataSet(DataFrame::empty()))
+            }
+            Command::Copy {
+                query,
+                target,
+                options,
+            }
+            | Command::Insert {
+                query,
+                target,
+                options,
+            } => {
+                let mut df = self.plan(query, sources).await?.collect()?;
+                export(&mut df, &target, &options, self.options.base_dir.as_deref())?;
+                // later statements read what was written, not what the target held.
+                sources.retain(|(source, _), _| *source != target);
+                self.frame_cache().invalidate(&target);
+                Ok(DataSet(df!("rows" => [df.height() as u64])?))
+            }
+            Command::Explain {
+                query,
+                analyze: run,
+            } => {
+                let statement = query.to_string();
+                self.profile = Some(Profile {
+                    analyze: run,
+                    ..Default::default()
+                });
+                let plan = self.plan(query, sources).await;
+                let profile = self.profile.take().unwrap_or_default();
+                match run {
+                    false => Ok(DataSet(explain(&statement, profile, &plan?)?)),
+                    true => Ok(DataSet(analyze(profile, plan?)?)),
+                }
+            }
+            Command::ShowTables => Ok(DataSet(self.show_tables()?)),
+            Command::Describe { source } => {
+                let options = ReadOptions::default();
+                let df = self
+                    .resolve(&source, &options, None, sources)
+                    .await?
+                    .collect()?;
+                DataSet(df).describe_schema()
+            }
+            Command::Drop {
+                kind,
+                names,
+                if_exists,
+            } => {
+                for name in names {
+                    match self.catalog.get(&name).map(TableSource::kind) {
+                        Some(found) if found == kind => {
+                            self.catalog.remove(&name);
+                        }
+                        Some(found) => {
+                            return Err(Error::catalog(
+                                format!("is a {found:?}, not a {kind:?}"),
+                                name,
+                            ))
+                        }
+                        None if if_exists => {}
+                        None => {
+                            let suggestions = suggest(&name, self.table_names());
+                            return Err(Error::unknown_table(name, suggestions));
+                        }
+                    }
+                }
+                Ok(DataSet(DataFrame::empty()))
+            }
+            Command::SetVariable { name, value } => {
+                self.variables.insert(name, value);
+                Ok(DataSet(DataFrame::empty()))
+            }
+            Command::CreateMacro {
+                name,
+                definition,
+                or_replace,
+            } => {
+                if self.macros.contains_key(&name) && !or_replace {
+                    return Err(Error::catalog("already exists", name));
+                }
+                self.macros.insert(name, definition);
+                Ok(DataSet(DataFrame::empty()))
+            }
+        }
+    }
+
+    /// Whether a `CREATE` of `name` should go ahead.
+    fn ensure_creatable(&self, name: &str, or_replace: bool, if_not_exists: bool) -> Result<bool> {
+        match self.catalog.contains_key(name) {
+            false => Ok(true),
+            true if or_replace => Ok(true),
+            true if if_not_exists => Ok(false),
+            true => Err(Error::catalog("already exists", name)),
+        }
+    }
+
+    /// Translate a query into a polars plan over its (loaded) data source.
+    async fn plan(&mut self, mut statement: Statement, sources: &mut Sources) -> Result<LazyFrame> {
+        // translate once before fetching, so unsupported queries fail fast.
+        let (source, options) = {
+            let sql = Sql::translate(&statement, &self.functions)?;
+            (sql.source.to_owned(), sql.read_options)
+        };
+        let lf = self
+            .resolve(&source, &options, Some(&statement), sources)
+            .await?;
+
+        let schema = lf.schema()?;
+        Binder::new(&schema).bind(&mut statement)?;
+        TypeChecker::new(&schema, self.options.coercion)
+            .with_functions(&self.functions)
+            .check(&mut statement)?;
+        let sql = Sql::translate(&statement, &self.functions)?;
+        debug!("translated {statement} into {sql:?}");
+        if let Some(profile) = self.profile.as_mut() {
+            profile.translated = Some(format!("{sql:#?}"));
+        }
+        let Sql {
+            condition,
+            selection,
+            group_by,
+            having,
+            offset,
+            limit,
+            order_by,
+            ..
+        } = sql;
+        let selection = match schema.contains(SOURCE_FILE) {
+            true => selection
+                .into_iter()
+                .map(|expr| match expr {
+                    Expr::Wildcard => expr.exclude([SOURCE_FILE]),
+                    expr => expr,
+                })
+                .collect(),
+            false => selection,
+        };
+
+        let lf = self.stage("scan", lf);
+        let filtered = match condition {
+            Some(expr) => lf.filter(expr),
+            None => lf,
+        };
+        let filtered = self.stage("filter", filtered);
+
+        if !group_by.is_empty() {
+            let aggregated = self.aggregate(filtered, group_by, having, selection)?;
+            let order_by = order_by
+                .into_iter()
+                .map(|(name, desc)| (col(&name), desc))
+                .collect();
+            let sorted = sort(aggregated, order_by);
+            let sorted = self.stage("sort", sorted);
+            let sliced = slice(sorted, offset, limit);
+            let sliced = self.stage("slice", sliced);
+            return Ok(sliced);
+        }
+
+        // the frame is sorted before the projection, so a key naming a select
+        // alias is sorted by the aliased expression.
+        let order_by = order_by
+            .into_iter()
+            .map(|(name, desc)| {
+                let aliased = selection.iter().find_map(|expr| match expr {
+                    Expr::Alias(inner, alias) if alias.as_ref() == name => Some(inner.as_ref()),
+                    _ => None,
+                });
+                (aliased.cloned().unwrap_or_else(|| col(&name)), desc)
+            })
+            // an aggregate without GROUP BY yields a single row to sort.
+            .filter(|(expr, _)| !is_aggregate(expr))
+            .collect();
+        let sorted = sort(filtered, order_by);
+        let sorted = self.stage("sort", sorted);
+        // sliced after the projection, so aggregates and windows see every row.
+        let projected = sorted.select(selection);
+        let projected = self.stage("projection", projected);
+        let sliced = slice(projected, offset, limit);
+        let sliced = self.stage("slice", sliced);
+        Ok(sliced)
+    }
+
+    /// Aggregate the selection per group, then keep the groups matching
+    /// `having` and put the columns back in selection order.
+    fn aggregate(
+        &mut self,
+        lf: LazyFrame,
+        keys: Vec<Expr>,
+        having: Option<Expr>,
+        selection: Vec<Expr>,
+    ) -> Result<LazyFrame> {
+        // where every selected expression ends up in the aggregated frame,
+        // which holds the keys followed by the aggregates.
+        enum Output {
+            Key(usize, Option<Arc<str>>),
+            Aggregate(usize),
+        }
+        let mut aggregates = vec![];
+        let outputs: Vec<_> = selection
+            .into_iter()
+            .map(|expr| {
+                let (inner, alias) = match &expr {
+                    Expr::Alias(inner, alias) => (inner.as_ref(), Some(alias.clone())),
+                    expr => (expr, None),
+                };
+                match keys.iter().position(|key| key == inner) {
+                    Some(i) => Output::Key(i, alias),
+                    None => {
+                        aggregates.push(expr);
+                        Output::Aggregate(keys.len() + aggregates.len() - 1)
+                    }
+                }
+            })
+            .collect();
+        if let Some(having) = &having {
+            aggregates.push(having.clone().alias(HAVING));
+        }
+
+        let grouped = lf.group_by_stable(keys).agg(aggregates);
+        let mut grouped = self.stage("aggregate", grouped);
+        if having.is_some() {
+            grouped = self.stage("having", grouped.filter(col(HAVING)));
+        }
+
+        let schema = grouped.schema()?;
+        let name = |i: usize| schema.get_at_index(i).map(|(name, _)| col(name));
+        let projection = outputs
+            .into_iter()
+            .filter_map(|output| match output {
+                Output::Key(i, Some(alias)) => name(i).map(|c| c.alias(&alias)),
+                Output::Key(i, None) | Output::Aggregate(i) => name(i),
+            })
+            .collect::<Vec<_>>();
+        let projected = grouped.select(projection);
+        let projected = self.stage("projection", projected);
+        Ok(projected)
+    }
+
+    /// Record a stage of the query when profiling.
+    fn stage(&mut self, name: &'static str, plan: LazyFrame) -> LazyFrame {
+        match self.profile.as_mut() {
+            Some(profile) => profile.stage(name, plan),
+            None => plan,
+        }
+    }
+
+    /// Look `name` up in the catalog, falling back to fetching it as a url, or
+    /// the standard input for `stdin` and `-`, read with `options` and
+    /// skipping the partitions ruled out by the condition of `statement`.
+    async fn resolve(
+        &mut self,
+        name: &str,
+        options: &ReadOptions,
+        statement: Option<&Statement>,
+        sources: &mut Sources,
+    ) -> Result<LazyFrame> {
+        if let Some(table) = information_schema(name) {
+            return self.information_schema(table).await.map(|df| df.lazy());
+        }
+        let registered = match self.catalog.contains_key(name) {
+            true => Some(name.to_owned()),
+            false => self
+                .catalog
+                .keys()
+                .find(|key| key.eq_ignore_ascii_case(name))
+                .cloned(),
+        };
+        if let Some(key) = registered {
+            return self.registered(&key).await;
+        }
+
+        let name = match name {
+            "-" => STDIN,
+            name if name.eq_ignore_ascii_case("stdin") => STDIN,
+            name => name,
+        };
+        if name != STDIN
+            && !name.contains("://")
+            && !is_local(name, self.options.base_dir.as_deref())
+        {
+            return Err(Error::unknown_table(
+                name,
+                suggest(name, self.table_names()),
+            ));
+        }
+        let key = (name.to_owned(), *options);
+        if let Some(df) = sources.get(&key) {
+            return Ok(df.clone().lazy());
+        }
+        let (df, pruned) = self.load(name, options, statement).await?;
+        // a pruned source only holds the rows of this query.
+        if !pruned {
+            sources.insert(key, df.clone());
+        }
+        Ok(df.lazy())
+    }
+}
+
+impl Session {
+    /// The plan of the registered table `key`, loading it if it is still a url.
+    async fn registered(&mut self, key: &str) -> Result<LazyFrame> {
+        let loaded = match self.catalog.get(key) {
+            Some(TableSource::Url(url)) => {
+                let url = url.clone();
+                Some(self.load(&url, &ReadOptions::default(), None).await?.0)
+            }
+            Some(TableSource::Content(bytes, options)) => {
+                let options = *options;
+                let content = Content::from_bytes(Metadata::default(), bytes.clone());
+                let fetched = read(key, content, &options).await?;
+                let df = detect_content(fetched.bytes, &fetched.metadata, options.format)
+                    .load()
+                    .map_err(Error::load)?;
+                Some(df.0)
+            }
+            _ => None,
+        };
+        if let Some(df) = loaded {
+            self.catalog
+                .insert(key.to_owned(), TableSource::DataFrame(df));
+        }
+        Ok(match &self.catalog[key] {
+            TableSource::View(lf) => lf.as_ref().clone(),
+            TableSource::DataFrame(df) => df.clone().lazy(),
+            TableSource::Url(_) | TableSource::Content(..) => {
+                unreachable!("registered urls and content are loaded above")
+            }
+        })
+    }
+
+    /// The condition of `statement`, with the identifiers naming partition
+    /// columns bound to them as the binder does once the files are loaded.
+    fn partition_condition(&self, statement: &Statement, partitions: &DataFrame) -> Option<Expr> {
+        let mut statement = statement.clone();
+        let schema = partitions.schema();
+        Binder::partial(&schema).bind(&mut statement).ok()?;
+        Sql::translate(&statement, &self.functions).ok()?.condition
+    }
+
+    /// Load every file matched by the glob pattern or directory `url`,
+    /// unioned by column name, with missing columns filled with nulls.
+    /// Hive style `key=value` directories become typed columns, and the
+    /// partitions the condition of `statement` rules out are not fetched at
+    /// all, in which case the returned flag is set.
+    async fn load(
+        &mut self,
+        url: &str,
+        options: &ReadOptions,
+        statement: Option<&Statement>,
+    ) -> Result<(DataFrame, bool)> {
+        let url = source_url(url, self.options.base_dir.as_deref());
+        let url = url.as_str();
+        let urls = self.fetchers.list(url).await?;
+        if urls.len() == 1 && urls[0] == url {
+            return Ok((self.load_file(url, options).await?, false));
+        }
+        if urls.is_empty() {
+            return Err(Error::fetch(url, "no files match the pattern"));
+        }
+        let partitions = partitions(url, &urls)?;
+        let condition = partitions
+            .as_ref()
+            .zip(statement)
+            .and_then(|(partitions, statement)| self.partition_condition(statement, partitions));
+        let keep = match (&partitions, condition) {
+            (Some(partitions), Some(condition)) => prune(partitions, &condition),
+            _ => vec![true; urls.len()],
+        };
+        let pruned = keep.contains(&false);
+        debug!(
+            "pruned {} of {} partitions of {url}",
+            keep.iter().filter(|keep| !**keep).count(),
+            urls.len()
+        );
+        // the index of each file, for its partition values, fetched only once.
+        let mut seen = HashSet::new();
+        let files: Vec<(usize, &str)> = urls
+            .iter()
+            .enumerate()
+            .filter(|(index, file)| keep[*index] && seen.insert(file.as_str()))
+            .map(|(index, file)| (index, file.as_str()))
+            .collect();
+        let paths: Vec<&str> = files.iter().map(|(_, file)| *file).collect();
+        let fetched = self.fetch_files(&paths, options).await?;
+        let mut frames = Vec::with_capacity(files.len());
+        for ((index, file), fetched) in files.into_iter().zip(fetched) {
+            let mut df = self.parse(fetched, options)?;
+            let height = df.height();
+            for column in partitions
+                .iter()
+                .flat_map(|partitions| partitions.get_columns())
+            {
+                df.with_column(column.new_from_index(index, height))?;
+            }
+            let source = StringChunked::full(SOURCE_FILE, file, height);
+            df.with_column(source.into_series())?;
+            frames.push(df);
+        }
+        let df = match frames.len() {
+            0 => {
+                // every partition is ruled out: nothing is fetched, the columns
+                // are the partition keys and those of any file loaded before.
+                let mut df = urls
+                    .iter()
+                    .find_map(|file| self.frame_cache().schema(file, options))
+                    .map(|schema| DataFrame::from(&schema))
+                    .unwrap_or_default();
+                for column in partitions
+                    .iter()
+                    .flat_map(|partitions| partitions.get_columns())
+                {
+                    df.with_column(column.clear())?;
+                }
+                df.with_column(Series::new_empty(SOURCE_FILE, &DataType::String))?;
+                df
+            }
+            1 => frames.remove(0),
+            _ => {
+                unify_dtypes(&mut frames)?;
+                polars::functions::concat_df_diagonal(&frames)?
+            }
+        };
+        Ok((df, pruned))
+    }
+
+    async fn load_file(&mut self, url: &str, options: &ReadOptions) -> Result<DataFrame> {
+        let fetched = fetch_file(&self.fetchers, url, options).await?;
+        self.parse(fetched, options)
+    }
+
+    /// Fetch `urls`, up to `max_concurrent_fetches` at a time, returned in
+    /// the order of `urls`.
+    async fn fetch_files(&self, urls: &[&str], options: &ReadOptions) -> Result<Vec<Fetched>> {
+        stream::iter(urls.iter().copied())
+            .map(|url| fetch_file(&self.fetchers, url, options))
+            .buffered(self.options.max_concurrent_fetches.max(1))
+            .try_collect()
+            .await
+    }
+
+    /// Load fetched content, unless the same content was parsed before.
+    fn parse(&mut self, fetched: Fetched, options: &ReadOptions) -> Result<DataFrame> {
+        let Fetched {
+            url,
+            metadata,
+            bytes,
+            elapsed,
+        } = fetched;
+        let start = Instant::now();
+        let key = FrameKey {
+            source: url.clone(),
+            options: *options,
+            fingerprint: fingerprint(&bytes),
+        };
+        let cached = self.frame_cache().get(&key);
+        let df = match cached {
+            Some(df) => {
+                debug!("{url} is unchanged, reusing its parsed content");
+                df
+            }
+            None => {
+                let df = detect_content(bytes, &metadata, options.format)
+                    .load()
+                    .map_err(Error::load)?
+                    .0;
+                self.frame_cache().insert(key, df.clone());
+                df
+            }
+        };
+        if let Some(profile) = self.profile.as_mut() {
+            profile.timings.push(Timing {
+                step: "fetch",
+                source: url.clone(),
+                rows: None,
+                elapsed,
+            });
+            profile.timings.push(Timing {
+                step: "load",
+                source: url,
+                rows: Some(df.height()),
+                elapsed: start.elapsed(),
+            });
+        }
+        Ok(df)
+    }
+
+    /// `SHOW TABLES`: name and type of the registered tables.
+    fn show_tables(&self) -> Result<DataFrame> {
+        let mut tables: Vec<_> = self.catalog.iter().collect();
+        tables.sort_by_key(|(name, _)| name.as_str());
+        let (names, types): (Vec<_>, Vec<_>) = tables
+            .into_iter()
+            .map(|(name, table)| {
+                let table_type = match table.kind() {
+                    TableKind::Table => "BASE TABLE",
+                    TableKind::View => "VIEW",
+                };
+                (name.as_str(), table_type)
+            })
+            .unzip();
+        Ok(df!("table_name" => names, "table_type" => types)?)
+    }
+
+    /// The virtual `information_schema.tables` and `information_schema.columns`.
+    async fn information_schema(&mut self, table: &str) -> Result<DataFrame> {
+        match table {
+            "tables" => self.show_tables(),
+            "columns" => {
+                let mut names: Vec<_> = self.catalog.keys().cloned().collect();
+                names.sort();
+                let (mut tables, mut columns, mut positions, mut types) =
+                    (vec![], vec![], vec![], vec![]);
+                for name in names {
+                    let schema = self.registered(&name).await?.schema()?;
+                    for (position, (column, dtype)) in schema.iter().enumerate() {
+                        tables.push(name.clone());
+                        columns.push(column.to_string());
+                        positions.push(position as u32 + 1);
+                        types.push(dtype.to_string());
+                    }
+                }
+                Ok(df!(
+                    "table_name" => tables,
+                    "column_name" => columns,
+                    "ordinal_position" => positions,
+                    "data_type" => types,
+                )?)
+            }
+            _ => Err(Error::unknown_table(
+                format!("information_schema.{table}"),
+                suggest(table, ["tables", "columns"].into_iter()),
+            )),
+        }
+    }
+}
+
+/// Cast the columns sharing a name across `frames` to their supertype, or to
+/// strings when their types have none, so the frames can be unioned.
+fn unify_dtypes(frames: &mut [DataFrame]) -> Result<()> {
+    let mut dtypes: HashMap<String, DataType> = HashMap::new();
+    for column in frames.iter().flat_map(|df| df.get_columns()) {
+        dtypes
+            .entry(column.name().to_owned())
+            .and_modify(|dtype| {
+                *dtype = get_supertype(dtype, column.dtype()).unwrap_or(DataType::String)
+            })
+            .or_insert_with(|| column.dtype().clone());
+    }
+    for df in frames.iter_mut() {
+        let columns: Vec<Series> = df
+            .get_columns()
+            .iter()
+            .filter(|column| column.dtype() != &dtypes[column.name()])
+            .map(|column| column.cast(&dtypes[column.name()]))
+            .collect::<PolarsResult<_>>()?;
+        for column in columns {
+            df.with_column(column)?;
+        }
+    }
+    Ok(())
+}
+
+/// Sort by the `(key, descending)` pairs of `order_by`, the first key first.
+fn sort(lf: LazyFrame, order_by: Vec<(Expr, bool)>) -> LazyFrame {
+    if order_by.is_empty() {
+        return lf;
+    }
+    let (exprs, descending): (Vec<_>, Vec<_>) = order_by.into_iter().unzip();
+    lf.sort_by_exprs(
+        exprs,
+        SortMultipleOptions::new().with_order_descendings(descending),
+    )
+}
+
+/// Whether `expr` aggregates the whole frame, outside of a window.
+fn is_aggregate(expr: &Expr) -> bool {
+    let nodes: Vec<&Expr> = expr.into_iter().collect();
+    nodes.iter().any(|node| matches!(node, Expr::Agg(_)))
+        && !nodes.iter().any(|node| matches!(node, Expr::Window { .. }))
+}
+
+fn slice(lf: LazyFrame, offset: Option<i64>, limit: Option<usize>) -> LazyFrame {
+    match offset.is_some() || limit.is_some() {
+        true => lf.slice(offset.unwrap_or(0), limit.unwrap_or(usize::MAX) as u32),
+        false => lf,
+    }
+}
+
+/// The table name inside `information_schema.`, if `name` refers to it.
+fn information_schema(name: &str) -> Option<&str> {
+    let (schema, table) = name.split_once('.')?;
+    schema
+        .eq_ignore_ascii_case("information_schema")
+        .then_some(table)
+}
+
+/// Write `df` to the local path or `file:` url `target`.
+fn export(
+    df: &mut DataFrame,
+    target: &str,
+    options: &WriteOptions,
+    base: Option<&Path>,
+) -> Result<()> {
+    let path = local_path(target, base)
+        .ok_or_else(|| Error::unsupported("writing to a non file:// target", target))?;
+    if options.append {
+        check_append(&path, options, &df.schema())?;
+    }
+    FileWriter::new(&path, options)
+        .write(df)
+        .map_err(|e| Error::write(target, e))
+}
+
+fn parse(sql: &str) -> Result<Vec<Statement>> {
+    let ast = Parser::parse_sql(&OrinDialect, sql)?;
+    debug!("parsed {} statement(s) from {sql}", ast.len());
+    Ok(ast)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{covid, TempDir};
+
+    #[tokio::test]
+    async fn it_queries_registered_tables() {
+        let mut session = Session::default();
+        session.register_table("covid", covid());
+        let ds = session
+            .query("select location from Covid where new_deaths > 100")
+            .await
+            .unwrap();
+        assert!(ds.height() > 0);
+        assert!(matches!(
+            session.catalog.get("covid"),
+            Some(TableSource::DataFrame(_))
+        ));
+
+        let df = df!("a" => [1, 2, 3]).unwrap();
+        session.register_dataframe("numbers", df);
+        let ds = session
+            .query("select a from numbers where a > 1")
+            .await
+            .unwrap();
+        assert_eq!(ds.height(), 2);
+    }
+
+    #[tokio::test]
+    async fn it_creates_and_drops_views_and_tables() {
+        let mut session = Session::default();
+        session.register_dataframe("numbers", df!("a" => [1, 2, 3, 4]).unwrap());
+        let script = "create view big as select a from numbers where a > 1; \
+            create table small as select a from numbers where a < 3; \
+            select a from big where a < 4";
+        let results = session.run_script(script).await.unwrap();
+        assert_eq!(results[2].as_ref().unwrap().height(), 2);
+        assert!(matches!(
+            session.catalog.get("big"),
+            Some(TableSource::View(_))
+        ));
+        assert!(matches!(
+            session.catalog.get("small"),
+            Some(TableSource::DataFrame(_))
+        ));
+
+        let err = session
+            .query("create view big as select a from numbers")
+            .await;
+        assert!(matches!(err, Err(Error::CatalogError { .. })));
+        session
+            .query("create or replace view big as select a from numbers where a > 3")
+            .await
+            .unwrap();
+        assert_eq!(
+            session.query("select a from big").await.unwrap().height(),
+            1
+        );
+
+        let err = session.query("drop view small").await.unwrap_err();
+        assert_eq!(err.to_string(), "`small` is a Table, not a View");
+        session.query("drop table small").await.unwrap();
+        session.query("drop view if exists small").await.unwrap();
+        assert!(session.query("drop table small").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn it_unions_glob_sources() {
+        let dir = TempDir::new("union");
+        std::fs::write(dir.join("2024-01.csv"), "a,b\n1,x\n2,y\n").unwrap();
+        std::fs::write(dir.join("2024-02.csv"), "b,c\nz,true\n").unwrap();
+        std::fs::write(dir.join("2023-12.csv"), "a\n0\n").unwrap();
+
+        let mut session = Session::default();
+        let sql = format!("select * from 'file://{}/2024-*.csv'", dir.display());
+        let ds = session.query(sql).await.unwrap();
+        assert_eq!(ds.get_column_names(), ["a", "b", "c"]);
+        assert_eq!(ds.column("a").unwrap().null_count(), 1);
+
+        let sql = format!(
+            "select _source_file, count(*) n from 'file://{}' group by _source_file",
+            dir.display()
+        );
+        let ds = session.query(sql).await.unwrap();
+        let files: Vec<_> = ds
+            .column(SOURCE_FILE)
+            .unwrap()
+            .str()
+            .unwrap()
+            .into_iter()
+            .map(|file| file.unwrap().rsplit('/').next().unwrap().to_owned())
+            .collect();
+        assert_eq!(files, ["2023-12.csv", "2024-01.csv", "2024-02.csv"]);
+    }
+
+    #[tokio::test]
+    async fn it_unions_conflicting_column_types() {
+        let dir = TempDir::new("dtypes");
+        std::fs::write(dir.join("1.csv"), "a,b\n1,x\n").unwrap();
+        std::fs::write(dir.join("2.csv"), "a,b\n2.5,2\n").unwrap();
+
+        let mut session = Session::default();
+        let sql = format!("select a, b from 'file://{}' order by a", dir.display());
+        let ds = session.query(sql).await.unwrap();
+        let a: Vec<_> = ds.column("a").unwrap().f64().unwrap().into_iter().collect();
+        assert_eq!(a, [Some(1.0), Some(2.5)]);
+        let b: Vec<_> = ds.column("b").unwrap().str().unwrap().into_iter().collect();
+        assert_eq!(b, [Some("x"), Some("2")]);
+    }
+
+    #[tokio::test]
+    async fn it_fetches_files_concurrently() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use async_trait::async_trait;
+        use url::Url;
+
+        use crate::fetcher::Content;
+
+        /// Lists the same file twice, and counts how many fetches overlap.
+        #[derive(Clone, Default)]
+        struct Slow {
+            running: Arc<AtomicUsize>,
+            most: Arc<AtomicUsize>,
+            fetched: Arc<AtomicUsize>,
+        }
+
+        #[async_trait]
+        impl Fetcher for Slow {
+            type Error = BoxError;
+
+            async fn fetch(&self, _: &Url) -> std::result::Result<Content, BoxError> {
+                let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+                self.most.fetch_max(running, Ordering::SeqCst);
+                self.fetched.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                self.running.fetch_sub(1, Ordering::SeqCst);
+                Ok(Content::from_bytes(Default::default(), "a\n1\n"))
+            }
+
+            async fn list(&self, _: &Url) -> std::result::Result<Vec<Url>, BoxError> {
+                let urls = ["1", "2", "3", "4", "5", "1"]
+                    .map(|name| Url::parse(&format!("slow://data/{name}.csv")).unwrap());
+                Ok(urls.to_vec())
+            }
+        }
+
+        let slow = Slow::default();
+        let mut session = Session::default();
+        session.options_mut().max_concurrent_fetches = 3;
+        session.register_fetcher("slow", slow.clone());
+        let ds = session
+            .query("select count(*) n from 'slow://data/'")
+            .await
+            .unwrap();
+        assert_eq!(ds.column("n").unwrap().u32().unwrap().get(0), Some(5));
+        assert_eq!(slow.fetched.load(Ordering::SeqCst), 5);
+        assert_eq!(slow.most.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn it_reads_relative_paths() {
+        let mut session = Session::default();
+        session.options_mut().base_dir = Some(env!("CARGO_MANIFEST_DIR").into());
+        let ds = session
+            .query("select count(*) n from 'owid-covid-latest.csv'")
+            .await
+            .unwrap();
+        assert_eq!(ds.height(), 1);
+        let ds = session
+            .query("select count(*) n from 'src/../owid-covid-latest.csv'")
+            .await
+            .unwrap();
+        assert_eq!(ds.height(), 1);
+        assert!(matches!(
+            session.query("select a from 'missing.csv'").await,
+            Err(Error::FetchError { .. })
+        ));
+        assert!(matches!(
+            session.query("select a from missing").await,
+            Err(Error::UnknownTable { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_reads_stdin_and_in_memory_content() {
+        use crate::fetcher::StdinFetcher;
+
+        let mut session = Session::default();
+        session.register_fetcher("stdin", StdinFetcher::from_reader(&b"a\n1\n2\n"[..]));
+        for sql in ["select sum(a) a from stdin", "select sum(a) a from '-'"] {
+            let ds = session.query(sql).await.unwrap();
+            assert_eq!(ds.column("a").unwrap().i64().unwrap().get(0), Some(3));
+        }
+
+        session.register_content(
+            "csv",
+            String::from("a,b\n1,x\n2,y\n"),
+            ReadOptions::default(),
+        );
+        let lines = b"{\"a\": 1}\n{\"a\": 5}\n".to_vec();
+        session.register_content("lines", &lines[..], ReadOptions::default());
+        drop(lines);
+        let ds = session
+            .query("select b from csv where a > 1")
+            .await
+            .unwrap();
+        assert_eq!(ds.column("b").unwrap().str().unwrap().get(0), Some("y"));
+        let ds = session.query("select max(a) a from lines").await.unwrap();
+        assert_eq!(ds.column("a").unwrap().i64().unwrap().get(0), Some(5));
+        assert!(matches!(
+            session.catalog.get("lines"),
+            Some(TableSource::DataFrame(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_describes_the_catalog() {
+        let mut session = Session::default();
+        session.register_table("covid", covid());
+        session.register_dataframe("numbers", df!("a" => [Some(1), None, Some(3)]).unwrap());
+        session
+            .query("create view big as select a from numbers where a > 1")
+            .await
+            .unwrap();
+
+        let tables = session.query("show tables").await.unwrap();
+        assert_eq!(tables.shape(), (3, 2));
+
+        let described = session.query("describe numbers").await.unwrap();
+        assert_eq!(
+            described.0,
+            df!(
+                "column_name" => ["a"],
+                "data_type" => ["i32"],
+                "null_count" => [1u64],
+                "sample" => ["1"],
+            )
+            .unwrap()
+        );
+        let described = session
+            .query(format!("show columns from {}", covid()))
+            .await
+            .unwrap();
+        assert_eq!(described.height(), 67);
+
+        let columns = session
+            .query(
+                "select column_name, data_type from information_schema.columns \
+                where table_name = 'big'",
+            )
+            .await
+            .unwrap();
+        assert_eq!(columns.shape(), (1, 2));
+        let tables = session
+            .query("select table_name from information_schema.tables where table_type = 'VIEW'")
+            .await
+            .unwrap();
+        assert_eq!(tables.height(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_orders_by_select_aliases() {
+        let mut session = Session::default();
+        let df = df!("a" => [2i64, 3, 1], "b" => ["y", "z", "x"]).unwrap();
+        session.register_dataframe("t", df);
+        let ds = session
+            .query("select a * 10 x, b from t order by x desc limit 2")
+            .await
+            .unwrap();
+        let x = ds.column("x").unwrap().i64().unwrap().to_vec();
+        assert_eq!(x, [Some(30), Some(20)]);
+        let ds = session
+            .query("select sum(a) total from t order by total")
+            .await
+            .unwrap();
+        assert_eq!(ds.height(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_limits_after_aggregates_and_windows() {
+        let mut session = Session::default();
+        let df = df!("a" => [1i64, 2, 3, 4], "g" => ["x", "x", "y", "y"]).unwrap();
+        session.register_dataframe("t", df);
+        let ds = session
+            .query("select sum(a) total from t limit 1")
+            .await
+            .unwrap();
+        let total = ds.column("total").unwrap().i64().unwrap().to_vec();
+        assert_eq!(total, [Some(10)]);
+        let ds = session
+            .query("select a, sum(a) over (partition by g) total from t order by a desc limit 2")
+            .await
+            .unwrap();
+        let total = ds.column("total").unwrap().i64().unwrap().to_vec();
+        assert_eq!(total, [Some(7), Some(7)]);
+    }
+
+    #[tokio::test]
+    async fn it_sorts_by_keys_in_order() {
+        let mut session = Session::default();
+        let df = df!("a" => [1i64, 2, 1, 2], "b" => [1i64, 2, 3, 4]).unwrap();
+        session.register_dataframe("t", df);
+        let ds = session
+            .query("select a, b from t order by a desc, b")
+            .await
+            .unwrap();
+        let b = ds.column("b").unwrap().i64().unwrap().to_vec();
+        assert_eq!(b, [Some(2), Some(4), Some(1), Some(3)]);
+        let ds = session
+            .query("select a, count(*) n from t group by a order by n, a desc")
+            .await
+            .unwrap();
+        let a = ds.column("a").unwrap().i64().unwrap().to_vec();
+        assert_eq!(a, [Some(2), Some(1)]);
+    }
+
+    #[tokio::test]
+    async fn it_rejects_empty_queries() {
+        let mut session = Session::default();
+        for sql in ["", ";", "  ;  "] {
+            assert!(matches!(
+                session.query(sql).await,
+                Err(Error::ParseError { span: None, .. })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn it_suggests_registered_tables() {
+        let mut session = Session::default();
+        session.register_table("covid", covid());
+        let err = session.query("select a from covd").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown table `covd`, did you mean `covid`?"
+        );
+    }
+}
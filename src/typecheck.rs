@@ -0,0 +1,524 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use polars::prelude::{DataType, Schema, TimeUnit};
+use polars_core::utils::get_supertype;
+use sqlparser::ast::{
+    BinaryOperator, CastKind, DataType as SqlDataType, Expr as SqlExpr, FunctionArg,
+    FunctionArgExpr, FunctionArguments, GroupByExpr, Query, SelectItem, SetExpr, Statement,
+    TimezoneInfo, UnaryOperator, Value,
+};
+
+use crate::{
+    convert::{function_args, SqlType},
+    error::{Error, Result},
+    udf::FunctionRegistry,
+};
+
+/// Implicit coercions applied by the [`TypeChecker`].
+///
+/// - `string_to_date`: a string literal compared with, or subtracted from, a
+///   date operand is rewritten into an explicit `CAST` to the date type, e.g.
+///   `last_updated_date > '2024-05-01'`.
+/// - `int_to_float`: integer and float operands may be mixed. No `CAST` is
+///   inserted, polars widens the integer side when the plan runs.
+///
+/// Numeric literals are untyped and always adapt to the other operand. Both
+/// coercions are enabled by default.
+#[derive(Debug, Clone, Copy)]
+pub struct Coercion {
+    pub string_to_date: bool,
+    pub int_to_float: bool,
+}
+
+impl Default for Coercion {
+    fn default() -> Self {
+        Coercion {
+            string_to_date: true,
+            int_to_float: true,
+        }
+    }
+}
+
+/// Infers the type of every expression of a bound statement from the source
+/// schema and reports type errors before anything is executed.
+pub struct TypeChecker<'a> {
+    schema: &'a Schema,
+    coercion: Coercion,
+    functions: Option<&'a FunctionRegistry>,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(schema: &'a Schema, coercion: Coercion) -> Self {
+        TypeChecker {
+            schema,
+            coercion,
+            functions: None,
+        }
+    }
+
+    /// Check calls to user defined functions against their declared types.
+    pub fn with_functions(mut self, functions: &'a FunctionRegistry) -> Self {
+        self.functions = Some(functions);
+        self
+    }
+
+    pub fn check(&self, statement: &mut Statement) -> Result<()> {
+        match statement {
+            Statement::Query(q) => self.check_query(q),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_query(&self, query: &mut Query) -> Result<()> {
+        if let SetExpr::Select(select) = query.body.as_mut() {
+            for item in select.projection.iter_mut() {
+                if let SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } = item
+                {
+                    self.infer(expr)?;
+                }
+            }
+            if let Some(expr) = select.selection.as_mut() {
+                self.condition("WHERE", expr)?;
+            }
+            if let GroupByExpr::Expressions(exprs) = &mut select.group_by {
+                for expr in exprs.iter_mut() {
+                    self.infer(expr)?;
+                }
+            }
+            if let Some(expr) = select.having.as_mut() {
+                self.condition("HAVING", expr)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn condition(&self, clause: &str, expr: &mut SqlExpr) -> Result<()> {
+        let dtype = self.infer(expr)?;
+        if !matches!(dtype, DataType::Boolean | DataType::Null) {
+            return Err(Error::type_mismatch(
+                format!("{clause} expects a boolean condition, got {dtype}"),
+                expr,
+            ));
+        }
+        Ok(())
+    }
+
+    /// The result type of `expr`. Operands may be rewritten to apply coercions.
+    pub fn infer(&self, expr: &mut SqlExpr) -> Result<DataType> {
+        let snippet = expr.to_string();
+        let dtype = match expr {
+            SqlExpr::Identifier(id) => self
+                .schema
+                .get(&id.value)
+                .cloned()
+                .unwrap_or(DataType::Unknown),
+            SqlExpr::Value(v) => literal_type(v),
+            SqlExpr::Nested(e) => self.infer(e)?,
+            SqlExpr::IsNull(e) | SqlExpr::IsNotNull(e) => {
+                self.infer(e)?;
+                DataType::Boolean
+            }
+            SqlExpr::Between {
+                expr: e, low, high, ..
+            } => {
+                self.compare(e, low, &snippet)?;
+                self.compare(e, high, &snippet)?;
+                DataType::Boolean
+            }
+            SqlExpr::InList { expr: e, list, .. } => {
+                for item in list.iter_mut() {
+                    self.compare(e, item, &snippet)?;
+                }
+                DataType::Boolean
+            }
+            SqlExpr::UnaryOp { op, expr: e } => {
+                let dtype = self.infer(e)?;
+                match op {
+                    UnaryOperator::Not => expect_boolean(dtype, &snippet)?,
+                    UnaryOperator::Minus | UnaryOperator::Plus if is_numeric(&dtype) => dtype,
+                    _ => {
+                        return Err(Error::type_mismatch(
+                            format!("operator {op} can not be applied to {dtype}"),
+                            snippet,
+                        ))
+                    }
+                }
+            }
+            SqlExpr::Cast {
+                expr: e, data_type, ..
+            } => {
+                self.infer(e)?;
+                DataType::try_from(SqlType(data_type))?
+            }
+            SqlExpr::TypedString { data_type, .. } => DataType::try_from(SqlType(data_type))?,
+            SqlExpr::Function(_) => self.infer_function(expr, &snippet)?,
+            SqlExpr::BinaryOp { left, op, right } => match op {
+                BinaryOperator::And | BinaryOperator::Or => {
+                    expect_boolean(self.infer(left)?, &snippet)?;
+                    expect_boolean(self.infer(right)?, &snippet)?;
+                    DataType::Boolean
+                }
+                BinaryOperator::Gt
+                | BinaryOperator::Lt
+                | BinaryOperator::GtEq
+                | BinaryOperator::LtEq
+                | BinaryOperator::Eq
+                | BinaryOperator::NotEq => {
+                    self.compare(left, right, &snippet)?;
+                    DataType::Boolean
+                }
+                BinaryOperator::Plus
+                | BinaryOperator::Minus
+                | BinaryOperator::Multiply
+                | BinaryOperator::Divide
+                | BinaryOperator::Modulo => {
+                    let op = op.clone();
+                    self.arithmetic(&op, left, right, &snippet)?
+                }
+                _ => DataType::Unknown,
+            },
+            _ => DataType::Unknown,
+        };
+        Ok(dtype)
+    }
+
+    fn infer_function(&self, expr: &mut SqlExpr, snippet: &str) -> Result<DataType> {
+        let SqlExpr::Function(f) = expr else {
+            return Ok(DataType::Unknown);
+        };
+        let name = f.name.to_string().to_lowercase();
+        // validates the argument list, so only plain arguments are left below.
+        function_args(f)?;
+        let mut args: Vec<&mut SqlExpr> = match &mut f.args {
+            FunctionArguments::List(list) => list
+                .args
+                .iter_mut()
+                .filter_map(|arg| match arg {
+                    FunctionArg::Unnamed(FunctionArgExpr::Expr(e)) => Some(e),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+
+        let signature = self
+            .functions
+            .and_then(|functions| functions.signature(&name));
+        if let Some((declared_args, return_type)) = signature {
+            if args.len() != declared_args.len() {
+                return Err(Error::type_mismatch(
+                    format!(
+                        "{name} expects {} arguments, got {}",
+                        declared_args.len(),
+                        args.len()
+                    ),
+                    snippet,
+                ));
+            }
+            for (arg, declared) in args.into_iter().zip(declared_args) {
+                let actual = self.infer(arg)?;
+                if !self.coerce(arg, &actual, declared) {
+                    return Err(Error::type_mismatch(
+                        format!("{name} expects a {declared} argument, got {actual}"),
+                        snippet,
+                    ));
+                }
+            }
+            return Ok(return_type.clone());
+        }
+
+        let arg = match args.first_mut() {
+            Some(e) => self.infer(e)?,
+            None => DataType::Null,
+        };
+        let mismatch = |expected: &str| {
+            Err(Error::type_mismatch(
+                format!(
+                    "{} expects {expected} argument, got {arg}",
+                    name.to_uppercase()
+                ),
+                snippet,
+            ))
+        };
+        Ok(match name.as_str() {
+            "count" => DataType::UInt32,
+            "sum" if arg.is_integer() => DataType::Int64,
+            "sum" if is_numeric(&arg) => arg,
+            "sum" => return mismatch("a numeric"),
+            "avg" | "mean" if is_numeric(&arg) => DataType::Float64,
+            "avg" | "mean" => return mismatch("a numeric"),
+            "min" | "max" if arg != DataType::Boolean => arg,
+            "min" | "max" => return mismatch("an orderable"),
+            "lower" | "upper" if is_string(&arg) => DataType::String,
+            "length" | "char_length" if is_string(&arg) => DataType::UInt32,
+            "lower" | "upper" | "length" | "char_length" => return mismatch("a string"),
+            _ => DataType::Unknown,
+        })
+    }
+
+    /// Check that `left` and `right` can be compared with each other.
+    fn compare(&self, left: &mut SqlExpr, right: &mut SqlExpr, snippet: &str) -> Result<()> {
+        let (l, r) = (self.infer(left)?, self.infer(right)?);
+        self.unify(left, &l, right, &r).ok_or_else(|| {
+            Error::type_mismatch(format!("can not compare {l} with {r}"), snippet)
+        })?;
+        Ok(())
+    }
+
+    fn arithmetic(
+        &self,
+        op: &BinaryOperator,
+        left: &mut SqlExpr,
+        right: &mut SqlExpr,
+        snippet: &str,
+    ) -> Result<DataType> {
+        let (l, r) = (self.infer(left)?, self.infer(right)?);
+        let mismatch = || {
+            Error::type_mismatch(
+                format!("operator {op} can not be applied to {l} and {r}"),
+                snippet,
+            )
+        };
+        let operand = self.unify(left, &l, right, &r).ok_or_else(mismatch)?;
+        match (op, operand) {
+            (BinaryOperator::Minus, DataType::Date) => {
+                Ok(DataType::Duration(TimeUnit::Milliseconds))
+            }
+            (BinaryOperator::Minus, DataType::Datetime(unit, _)) => Ok(DataType::Duration(unit)),
+            (BinaryOperator::Divide, dtype) if is_numeric(&dtype) => Ok(DataType::Float64),
+            (_, dtype) if is_numeric(&dtype) || unknown(&dtype) => Ok(dtype),
+            _ => Err(mismatch()),
+        }
+    }
+
+    /// Whether `expr` of type `actual` can be passed where `declared` is
+    /// expected, rewriting it when a coercion applies.
+    fn coerce(&self, expr: &mut SqlExpr, actual: &DataType, declared: &DataType) -> bool {
+        if actual == declared || unknown(actual) || unknown(declared) {
+            return true;
+        }
+        if actual.is_numeric() && declared.is_numeric() {
+            return match declared.is_float() {
+                true => actual.is_float() || self.coercion.int_to_float || is_number_literal(expr),
+                false => actual.is_integer(),
+            };
+        }
+        if self.coercion.string_to_date && is_temporal(declared) && is_string_literal(expr) {
+            cast(expr, declared);
+            return true;
+        }
+        false
+    }
+
+    /// The common type of two operands, applying the configured coercions.
+    fn unify(
+        &self,
+        left: &mut SqlExpr,
+        l: &DataType,
+        right: &mut SqlExpr,
+        r: &DataType,
+    ) -> Option<DataType> {
+        if unknown(l) || l == r {
+            return Some(r.clone());
+        }
+        if unknown(r) {
+            return Some(l.clone());
+        }
+        if is_numeric(l) && is_numeric(r) {
+            let mixed = l.is_float() != r.is_float();
+            return match (is_number_literal(left), is_number_literal(right)) {
+                // an untyped literal takes the type of the other operand.
+                (true, false) if r.is_float() || !l.is_float() => Some(r.clone()),
+                (false, true) if l.is_float() || !r.is_float() => Some(l.clone()),
+                (false, false) if mixed && !self.coercion.int_to_float => None,
+                _ => get_supertype(l, r),
+            };
+        }
+        if self.coercion.string_to_date {
+            if is_temporal(l) && is_string_literal(right) {
+                cast(right, l);
+                return Some(l.clone());
+            }
+            if is_temporal(r) && is_string_literal(left) {
+                cast(left, r);
+                return Some(r.clone());
+            }
+        }
+        None
+    }
+}
+
+fn literal_type(value: &Value) -> DataType {
+    match value {
+        Value::Number(n, _) if n.parse::<i64>().is_ok() => DataType::Int64,
+        Value::Number(..) => DataType::Float64,
+        Value::SingleQuotedString(_) | Value::DoubleQuotedString(_) => DataType::String,
+        Value::Boolean(_) => DataType::Boolean,
+        Value::Null => DataType::Null,
+        _ => DataType::Unknown,
+    }
+}
+
+fn expect_boolean(dtype: DataType, snippet: &str) -> Result<DataType> {
+    match dtype {
+        DataType::Boolean | DataType::Null | DataType::Unknown => Ok(DataType::Boolean),
+        dtype => Err(Error::type_mismatch(
+            format!("expected a boolean operand, got {dtype}"),
+            snippet,
+        )),
+    }
+}
+
+/// Rewrite `expr` into `CAST(expr AS dtype)`.
+fn cast(expr: &mut SqlExpr, dtype: &DataType) {
+    let data_type = match dtype {
+        DataType::Date => SqlDataType::Date,
+        _ => SqlDataType::Timestamp(None, TimezoneInfo::None),
+    };
+    let inner = std::mem::replace(expr, SqlExpr::Value(Value::Null));
+    *expr = SqlExpr::Cast {
+        kind: CastKind::Cast,
+        expr: Box::new(inner),
+        data_type,
+        format: None,
+    };
+}
+
+fn unknown(dtype: &DataType) -> bool {
+    matches!(dtype, DataType::Unknown | DataType::Null)
+}
+
+fn is_numeric(dtype: &DataType) -> bool {
+    dtype.is_numeric() || unknown(dtype)
+}
+
+fn is_string(dtype: &DataType) -> bool {
+    matches!(dtype, DataType::String) || unknown(dtype)
+}
+
+fn is_temporal(dtype: &DataType) -> bool {
+    matches!(dtype, DataType::Date | DataType::Datetime(..))
+}
+
+fn is_string_literal(expr: &SqlExpr) -> bool {
+    matches!(expr, SqlExpr::Value(Value::SingleQuotedString(_)))
+}
+
+fn is_number_literal(expr: &SqlExpr) -> bool {
+    match expr {
+        SqlExpr::Value(Value::Number(..)) => true,
+        SqlExpr::UnaryOp { expr, .. } | SqlExpr::Nested(expr) => is_number_literal(expr),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dialect::OrinDialect;
+    use polars::prelude::Field;
+    use sqlparser::parser::Parser;
+
+    fn schema() -> Schema {
+        Schema::from_iter([
+            Field::new("location", DataType::String),
+            Field::new("new_cases", DataType::Float64),
+            Field::new("population", DataType::Int64),
+            Field::new("last_updated_date", DataType::Date),
+        ])
+    }
+
+    fn check(sql: &str, coercion: Coercion) -> Result<String> {
+        let mut statement = Parser::parse_sql(&OrinDialect, sql).unwrap().remove(0);
+        TypeChecker::new(&schema(), coercion).check(&mut statement)?;
+        Ok(statement.to_string())
+    }
+
+    #[test]
+    fn it_reports_type_mismatches() {
+        let err = check(
+            "select location from t where location > 5",
+            Coercion::default(),
+        );
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "type mismatch in `location > 5`: can not compare str with i64"
+        );
+        let err = check("select sum(location) from t", Coercion::default());
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "type mismatch in `sum(location)`: SUM expects a numeric argument, got str"
+        );
+    }
+
+    #[test]
+    fn it_coerces_strings_to_dates() {
+        assert_eq!(
+            check(
+                "select last_updated_date - '2024-01-01' from t",
+                Coercion::default()
+            )
+            .unwrap(),
+            "SELECT last_updated_date - CAST('2024-01-01' AS DATE) FROM t"
+        );
+        let no_dates = Coercion {
+            string_to_date: false,
+            ..Default::default()
+        };
+        assert!(check("select last_updated_date - '2024-01-01' from t", no_dates).is_err());
+    }
+
+    #[test]
+    fn it_widens_integers_when_allowed() {
+        let sql = "select new_cases + population from t where new_cases > 10";
+        assert!(check(sql, Coercion::default()).is_ok());
+        let strict = Coercion {
+            int_to_float: false,
+            ..Default::default()
+        };
+        assert!(check(sql, strict).is_err());
+        assert!(check("select new_cases from t where new_cases > 10", strict).is_ok());
+    }
+
+    #[test]
+    fn it_unifies_numbers_to_the_wider_type() {
+        let schema = Schema::from_iter([
+            Field::new("small", DataType::Int32),
+            Field::new("big", DataType::Int64),
+            Field::new("ratio", DataType::Float32),
+        ]);
+        let checker = TypeChecker::new(&schema, Coercion::default());
+        let infer = |sql: &str| {
+            let mut expr = Parser::new(&OrinDialect)
+                .try_with_sql(sql)
+                .unwrap()
+                .parse_expr()
+                .unwrap();
+            checker.infer(&mut expr).unwrap()
+        };
+        assert_eq!(infer("small + small"), DataType::Int32);
+        assert_eq!(infer("small + 1"), DataType::Int32);
+        assert_eq!(infer("small * big"), DataType::Int64);
+        assert_eq!(infer("ratio - 2"), DataType::Float32);
+        assert_eq!(infer("small / ratio"), DataType::Float64);
+    }
+}
@@ -0,0 +1,316 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use polars::prelude::*;
+
+type SeriesFn = dyn Fn(&[Series]) -> PolarsResult<Series> + Send + Sync;
+
+/// A scalar function implemented in rust, called with one series per argument
+/// and expected to return a series of the same length.
+pub struct ScalarUdf {
+    name: String,
+    args: Vec<DataType>,
+    return_type: DataType,
+    fun: Arc<SeriesFn>,
+}
+
+impl ScalarUdf {
+    pub fn new<F>(
+        name: impl Into<String>,
+        args: Vec<DataType>,
+        return_type: DataType,
+        fun: F,
+    ) -> Self
+    where
+        F: Fn(&[Series]) -> PolarsResult<Series> + Send + Sync + 'static,
+    {
+        ScalarUdf {
+            name: name.into(),
+            args,
+            return_type,
+            fun: Arc::new(fun),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The declared argument types, checked by the type checker.
+    pub fn args(&self) -> &[DataType] {
+        &self.args
+    }
+
+    pub fn return_type(&self) -> &DataType {
+        &self.return_type
+    }
+
+    /// The polars expression calling this function over `args`, each cast to
+    /// its declared type.
+    pub fn call(&self, args: Vec<Expr>) -> Expr {
+        let args: Vec<_> = args
+            .into_iter()
+            .zip(&self.args)
+            .map(|(arg, dtype)| arg.cast(dtype.clone()))
+            .collect();
+        let fun = self.fun.clone();
+        map_multiple(
+            move |series: &mut [Series]| fun(series).map(Some),
+            args,
+            GetOutput::from_type(self.return_type.clone()),
+        )
+    }
+}
+
+/// An aggregate function implemented in rust. It is called once per group
+/// (or window partition) with one series per argument and returns a series
+/// holding a single value.
+pub struct AggregateUdf {
+    name: String,
+    args: Vec<DataType>,
+    return_type: DataType,
+    fun: Arc<SeriesFn>,
+}
+
+impl AggregateUdf {
+    pub fn new<F>(
+        name: impl Into<String>,
+        args: Vec<DataType>,
+        return_type: DataType,
+        fun: F,
+    ) -> Self
+    where
+        F: Fn(&[Series]) -> PolarsResult<Series> + Send + Sync + 'static,
+    {
+        AggregateUdf {
+            name: name.into(),
+            args,
+            return_type,
+            fun: Arc::new(fun),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn args(&self) -> &[DataType] {
+        &self.args
+    }
+
+    pub fn return_type(&self) -> &DataType {
+        &self.return_type
+    }
+
+    /// The polars expression aggregating `args`, applied group wise inside a
+    /// group by or window and over the whole column otherwise.
+    pub fn call(&self, args: Vec<Expr>) -> Expr {
+        let args: Vec<_> = args
+            .into_iter()
+            .zip(&self.args)
+            .map(|(arg, dtype)| arg.cast(dtype.clone()))
+            .collect();
+        let fun = self.fun.clone();
+        apply_multiple(
+            move |series: &mut [Series]| fun(series).map(Some),
+            args,
+            GetOutput::from_type(self.return_type.clone()),
+            true,
+        )
+    }
+}
+
+/// The user defined functions of a session, looked up by lowercase name.
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    scalars: HashMap<String, Arc<ScalarUdf>>,
+    aggregates: HashMap<String, Arc<AggregateUdf>>,
+}
+
+impl FunctionRegistry {
+    /// Register a scalar function, replacing any function of the same name.
+    pub fn register_scalar(&mut self, udf: ScalarUdf) {
+        let name = udf.name.to_lowercase();
+        self.aggregates.remove(&name);
+        self.scalars.insert(name, Arc::new(udf));
+    }
+
+    /// Register an aggregate function, replacing any function of the same name.
+    pub fn register_aggregate(&mut self, udaf: AggregateUdf) {
+        let name = udaf.name.to_lowercase();
+        self.scalars.remove(&name);
+        self.aggregates.insert(name, Arc::new(udaf));
+    }
+
+    pub fn scalar(&self, name: &str) -> Option<&ScalarUdf> {
+        self.scalars.get(&name.to_lowercase()).map(Arc::as_ref)
+    }
+
+    pub fn aggregate(&self, name: &str) -> Option<&AggregateUdf> {
+        self.aggregates.get(&name.to_lowercase()).map(Arc::as_ref)
+    }
+
+    /// The declared argument and return types of the function `name`.
+    pub fn signature(&self, name: &str) -> Option<(&[DataType], &DataType)> {
+        match self.scalar(name) {
+            Some(udf) => Some((udf.args(), udf.return_type())),
+            None => self
+                .aggregate(name)
+                .map(|udaf| (udaf.args(), udaf.return_type())),
+        }
+    }
+
+    pub fn deregister(&mut self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.scalars.remove(&name).is_some() | self.aggregates.remove(&name).is_some()
+    }
+}
+
+impl fmt::Debug for FunctionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FunctionRegistry")
+            .field("scalars", &self.scalars.keys().collect::<Vec<_>>())
+            .field("aggregates", &self.aggregates.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{data_set::DataSet, error::Error, session::Session};
+
+    #[tokio::test]
+    async fn it_calls_user_defined_functions() {
+        let mut session = Session::default();
+        session.register_dataframe("numbers", df!("a" => [1i64, 2, 3]).unwrap());
+        session.register_udf(
+            "Scale",
+            vec![DataType::Int64, DataType::Float64],
+            DataType::Float64,
+            |series| {
+                let factor = series[1].f64()?.get(0).unwrap_or(1.0);
+                Ok(series[0].cast(&DataType::Float64)? * factor)
+            },
+        );
+        let ds = session
+            .query("select scale(a, 1.5) scaled from numbers where scale(a, 2) > 3")
+            .await
+            .unwrap();
+        let scaled: Vec<_> = ds
+            .column("scaled")
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(scaled, [Some(3.0), Some(4.5)]);
+
+        let err = session
+            .query("select scale(a) from numbers")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "type mismatch in `scale(a)`: scale expects 2 arguments, got 1"
+        );
+        let err = session
+            .query("select scale('x', 1.0) from numbers")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { .. }));
+    }
+
+    #[tokio::test]
+    async fn it_calls_user_defined_aggregates() {
+        let mut session = Session::default();
+        session.register_dataframe(
+            "cases",
+            df!(
+                "continent" => ["Asia", "Europe", "Asia", "Europe", "Africa"],
+                "cases" => [10.0, 1.0, 20.0, 3.0, 5.0],
+                "population" => [3i64, 1, 1, 1, 2],
+            )
+            .unwrap(),
+        );
+        session.register_udaf(
+            "weighted_avg",
+            vec![DataType::Float64, DataType::Float64],
+            DataType::Float64,
+            |series| {
+                let weights = series[1].sum::<f64>()?;
+                let total = (&series[0] * &series[1]).sum::<f64>()?;
+                Ok(Series::new("weighted_avg", [total / weights]))
+            },
+        );
+        session.register_udaf(
+            "quantile",
+            vec![DataType::Float64, DataType::Float64],
+            DataType::Float64,
+            |series| {
+                let q = series[1].f64()?.get(0).unwrap_or(0.5);
+                let value = series[0]
+                    .f64()?
+                    .quantile(q, QuantileInterpolOptions::Linear)?;
+                Ok(Series::new("quantile", [value]))
+            },
+        );
+        let floats = |ds: &DataSet, name: &str| -> Vec<Option<f64>> {
+            ds.column(name)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .collect()
+        };
+
+        let ds = session
+            .query(
+                "select continent c, weighted_avg(cases, population) avg, quantile(cases, 0.5) \
+                from cases group by continent having count(*) > 1",
+            )
+            .await
+            .unwrap();
+        assert_eq!(ds.get_column_names(), ["c", "avg", "cases"]);
+        assert_eq!(floats(&ds, "avg"), [Some(12.5), Some(2.0)]);
+        assert_eq!(floats(&ds, "cases"), [Some(15.0), Some(2.0)]);
+
+        let ds = session
+            .query(
+                "select cases, weighted_avg(cases, population) over (partition by continent) avg \
+                from cases where continent <> 'Africa'",
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            floats(&ds, "avg"),
+            [Some(12.5), Some(2.0), Some(12.5), Some(2.0)]
+        );
+
+        let err = session
+            .query("select weighted_avg(cases) from cases")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::TypeMismatch { .. }));
+    }
+}
@@ -0,0 +1,283 @@
+// MIT License
+//
+// Copyright (c) 2024 hu5ky
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::{
+    fs::{self, File, OpenOptions},
+    path::Path,
+};
+
+use anyhow::{anyhow, Error, Result};
+use polars::prelude::*;
+
+/// How many lines of an appended file are read to infer its column types.
+const SAMPLE: usize = 100;
+
+/// The file formats results can be written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileFormat {
+    Csv,
+    NdJson,
+    Parquet,
+}
+
+impl FileFormat {
+    /// The format of a `(FORMAT name)` option.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "csv" => Some(FileFormat::Csv),
+            "json" | "ndjson" | "jsonl" => Some(FileFormat::NdJson),
+            "parquet" => Some(FileFormat::Parquet),
+            _ => None,
+        }
+    }
+
+    /// The format implied by the extension of `path`.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        Self::from_name(path.as_ref().extension()?.to_str()?)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// detected from the file extension when missing.
+    pub format: Option<FileFormat>,
+    /// csv only, defaults to `,`.
+    pub delimiter: Option<u8>,
+    /// csv only, defaults to writing a header unless appending to a non empty file.
+    pub header: Option<bool>,
+    /// append to the file instead of replacing it. Not supported for parquet.
+    pub append: bool,
+}
+
+pub trait Writer {
+    type Error;
+    fn write(self, df: &mut DataFrame) -> Result<(), Self::Error>;
+}
+
+pub struct FileWriter<'a> {
+    path: &'a Path,
+    options: &'a WriteOptions,
+}
+
+impl<'a> FileWriter<'a> {
+    pub fn new(path: &'a Path, options: &'a WriteOptions) -> Self {
+        FileWriter { path, options }
+    }
+}
+
+impl<'a> Writer for FileWriter<'a> {
+    type Error = Error;
+
+    fn write(self, df: &mut DataFrame) -> Result<(), Self::Error> {
+        let WriteOptions {
+            format,
+            delimiter,
+            header,
+            append,
+        } = self.options;
+        let format = format
+            .or_else(|| FileFormat::from_path(self.path))
+            .ok_or_else(|| anyhow!("can not detect the file format of {:?}", self.path))?;
+        if *append && format == FileFormat::Parquet {
+            return Err(anyhow!("appending to parquet files is not supported"));
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let existing = fs::metadata(self.path).map_or(0, |m| m.len());
+        let file = match append {
+            true => OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.path)?,
+            false => File::create(self.path)?,
+        };
+
+        match format {
+            FileFormat::Csv => CsvWriter::new(file)
+                .include_header(header.unwrap_or(!*append || existing == 0))
+                .with_separator(delimiter.unwrap_or(b','))
+                .finish(df)?,
+            FileFormat::NdJson => JsonWriter::new(file)
+                .with_json_format(JsonFormat::JsonLines)
+                .finish(df)?,
+            FileFormat::Parquet => {
+                ParquetWriter::new(file).finish(df)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check that rows with `schema` can be appended to the csv or json lines
+/// file at `path`: they need the columns of the file, in the same order and
+/// of compatible types. Csv files are expected to start with a header.
+pub fn check_append(
+    path: &Path,
+    options: &WriteOptions,
+    schema: &Schema,
+) -> crate::error::Result<()> {
+    use crate::error::Error;
+
+    let target = path.display().to_string();
+    let existing = match target_schema(path, options) {
+        Ok(Some(existing)) => existing,
+        Ok(None) => return Ok(()),
+        Err(e) => return Err(Error::write(target, e)),
+    };
+    if existing.len() != schema.len() {
+        let message = format!(
+            "appending {} columns to the {} columns of the file",
+            schema.len(),
+            existing.len()
+        );
+        return Err(Error::type_mismatch(message, target));
+    }
+    for ((name, dtype), (column, expected)) in schema.iter().zip(existing.iter()) {
+        if name != column {
+            let message = format!("appending column `{name}` where the file has `{column}`");
+            return Err(Error::type_mismatch(message, target));
+        }
+        if !appendable(expected, dtype) {
+            let message = format!("appending {dtype} to column `{name}` of type {expected}");
+            return Err(Error::type_mismatch(message, target));
+        }
+    }
+    Ok(())
+}
+
+/// The columns of the non empty csv or json lines file at `path`.
+fn target_schema(path: &Path, options: &WriteOptions) -> Result<Option<Schema>> {
+    if fs::metadata(path).map_or(0, |m| m.len()) == 0 {
+        return Ok(None);
+    }
+    let df = match options.format.or_else(|| FileFormat::from_path(path)) {
+        Some(FileFormat::Csv) => CsvReader::from_path(path)?
+            .with_separator(options.delimiter.unwrap_or(b','))
+            .infer_schema(Some(SAMPLE))
+            .with_try_parse_dates(true)
+            .with_n_rows(Some(SAMPLE))
+            .finish()?,
+        Some(FileFormat::NdJson) => JsonLineReader::from_path(path)?
+            .infer_schema_len(Some(SAMPLE))
+            .with_n_rows(Some(SAMPLE))
+            .finish()?,
+        Some(FileFormat::Parquet) | None => return Ok(None),
+    };
+    Ok(Some(df.schema()))
+}
+
+/// Whether values of type `appended` read back as the `existing` type.
+fn appendable(existing: &DataType, appended: &DataType) -> bool {
+    existing == appended
+        || (existing.is_integer() && appended.is_integer())
+        || (existing.is_float() && appended.is_numeric())
+        || matches!(
+            (existing, appended),
+            (DataType::Null, _) | (DataType::Datetime(..), DataType::Datetime(..))
+        )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{session::Session, test_util::TempDir};
+
+    #[test]
+    fn it_checks_appended_columns() {
+        let dir = TempDir::new("append");
+        let path = dir.join("log.csv");
+        let options = WriteOptions {
+            append: true,
+            ..Default::default()
+        };
+        let mut df = df!("a" => [1i64, 2], "b" => ["x", "y"]).unwrap();
+        check_append(&path, &options, &df.schema()).unwrap();
+        FileWriter::new(&path, &options).write(&mut df).unwrap();
+
+        let narrower = df!("a" => [3i32], "b" => ["z"]).unwrap();
+        check_append(&path, &options, &narrower.schema()).unwrap();
+        let renamed = df!("a" => [3i64], "c" => ["z"]).unwrap();
+        let err = check_append(&path, &options, &renamed.schema()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("appending column `c` where the file has `b`"));
+        let retyped = df!("a" => ["3"], "b" => ["z"]).unwrap();
+        let err = check_append(&path, &options, &retyped.schema()).unwrap_err();
+        assert!(err.is_query_error());
+        let wider = df!("a" => [3i64], "b" => ["z"], "c" => [true]).unwrap();
+        assert!(check_append(&path, &options, &wider.schema()).is_err());
+
+        let path = dir.join("log.ndjson");
+        FileWriter::new(&path, &options).write(&mut df).unwrap();
+        check_append(&path, &options, &df.schema()).unwrap();
+        assert!(check_append(&path, &options, &renamed.schema()).is_err());
+    }
+
+    #[tokio::test]
+    async fn it_writes_results_to_files() {
+        let dir = TempDir::new("export");
+        let (parquet, csv) = (dir.join("top.parquet"), dir.join("log.csv"));
+        let mut session = Session::default();
+        session.register_dataframe("numbers", df!("a" => [1, 2, 3, 4]).unwrap());
+
+        let copy = format!(
+            "copy (select a from numbers where a > 2) to 'file://{}' (format parquet)",
+            parquet.display()
+        );
+        let ds = session.query(copy).await.unwrap();
+        assert_eq!(ds.column("rows").unwrap().u64().unwrap().get(0), Some(2));
+        let written = ParquetReader::new(std::fs::File::open(&parquet).unwrap())
+            .finish()
+            .unwrap();
+        assert_eq!(written.height(), 2);
+
+        let insert = format!(
+            "insert into 'file://{}' select a from numbers",
+            csv.display()
+        );
+        session.query(&insert).await.unwrap();
+        session.query(&insert).await.unwrap();
+        let written = std::fs::read_to_string(&csv).unwrap();
+        assert_eq!(written.lines().count(), 9);
+
+        let mismatched = format!(
+            "insert into 'file://{}' select a b from numbers",
+            csv.display()
+        );
+        let err = session.query(mismatched).await.unwrap_err();
+        assert!(matches!(err, crate::Error::TypeMismatch { .. }), "{err}");
+
+        let count = format!("select count(*) n from 'file://{}'", csv.display());
+        let script = format!("{count}; {insert}; {count}");
+        let results = session.run_script(script).await.unwrap();
+        let counts: Vec<_> = results
+            .into_iter()
+            .map(|ds| {
+                let column = ds.unwrap().get_columns()[0]
+                    .cast(&DataType::UInt64)
+                    .unwrap();
+                column.u64().unwrap().get(0)
+            })
+            .collect();
+        assert_eq!(counts, [Some(8), Some(4), Some(12)]);
+    }
+}